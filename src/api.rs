@@ -1,89 +1,475 @@
-use indexmap::map::IndexMap;
+use std::fmt;
+use std::iter::FusedIterator;
 
 use crate::Lines;
 use crate::error::Error;
-use crate::types::{Entries, Props, Struct};
-pub use crate::types::Value;
+use crate::util::line_text;
+use crate::render::{render, RenderConfig};
+use crate::diagnostic::to_json;
+use crate::types::{Entries, Props, Struct, Meta};
+use crate::dom::{Structure, DomError};
+pub use crate::types::{Value, Span, ValueSpan, Definition, StructKind, ConversionError};
 
 
 #[derive(Debug)]
 pub struct JaclError<'src> {
-    internal: Error<'src>,
+    internal: Vec<Error>,
     input: &'src str,
     lines: Lines,
 }
 
 impl<'src> JaclError<'src> {
-    pub fn from_error(err: Error<'src>, input: &'src str, lines: Lines) -> JaclError<'src> {
+    pub fn from_error(err: Error, input: &'src str, lines: Lines) -> JaclError<'src> {
+        JaclError::from_errors(vec![err], input, lines)
+    }
+
+    pub fn from_errors(errs: Vec<Error>, input: &'src str, lines: Lines) -> JaclError<'src> {
+        assert!(!errs.is_empty(), "JaclError requires at least one error");
         JaclError {
-            internal: err,
+            internal: errs,
             input,
             lines,
         }
     }
 
+    /// Every error found in the input, in the order they were encountered
+    pub fn errors(&self) -> &[Error] {
+        &self.internal
+    }
+
     pub fn render(&self) -> String {
-        self.internal.render(self.input, &self.lines)
+        self.render_with(&RenderConfig::default())
+    }
+
+    pub fn render_with(&self, config: &RenderConfig) -> String {
+        self.internal.iter()
+            .map(|err| err.render_with(self.input, &self.lines, config))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Every error as JSON, one per line. See `Error::to_json`.
+    pub fn to_json_lines(&self, file: Option<&str>) -> String {
+        self.internal.iter()
+            .map(|err| format!("{}\n", err.to_json(self.input, &self.lines, file)))
+            .collect::<String>()
+    }
+
+    /// Keep the errors with the lines of source they point at, so they
+    /// can outlive the input
+    pub fn into_owned(self) -> OwnedJaclError {
+        let mut excerpt: Vec<SourceLine> = Vec::new();
+        let spans = self.internal.iter()
+            .flat_map(|err| err.span().into_iter().chain(err.secondary_labels().iter().map(|label| label.span)))
+            .collect::<Vec<Span>>();
+        for span in spans {
+            let last = span.end.saturating_sub(1).max(span.start);
+            let end_line = self.lines.iter()
+                .position(|&(_, end)| last < end)
+                .map_or(span.line, |idx| idx + 1)
+                .max(span.line);
+            let first_line = span.line.saturating_sub(OWNED_CONTEXT).max(1);
+            for lno in first_line..=end_line + OWNED_CONTEXT {
+                if excerpt.iter().any(|line| line.line == lno) {
+                    continue;
+                }
+                if let Some(text) = line_text(self.input, &self.lines, lno) {
+                    excerpt.push(SourceLine {
+                        line: lno,
+                        start: self.lines[lno - 1].0,
+                        text: text.to_string(),
+                    });
+                }
+            }
+        }
+        excerpt.sort_by_key(|line| line.line);
+        OwnedJaclError {
+            internal: self.internal,
+            excerpt,
+        }
     }
 }
 
+impl fmt::Display for JaclError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render().trim_end())
+    }
+}
+
+impl std::error::Error for JaclError<'_> {}
+
+/// Lines of context kept around each span by `into_owned`
+pub const OWNED_CONTEXT: usize = 3;
+
+#[derive(Clone, Debug)]
+struct SourceLine {
+    line: usize,
+    start: usize,
+    text: String,
+}
+
+/// A JaclError which owns the lines of source it needs, so it can be
+/// kept after the input is dropped or sent to another thread
+#[derive(Clone, Debug)]
+pub struct OwnedJaclError {
+    internal: Vec<Error>,
+    excerpt: Vec<SourceLine>,
+}
+
+impl OwnedJaclError {
+    /// Every error found in the input, in the order they were encountered
+    pub fn errors(&self) -> &[Error] {
+        &self.internal
+    }
+
+    pub fn render(&self) -> String {
+        self.render_with(&RenderConfig::default())
+    }
+
+    fn line_at(&self, lno: usize) -> Option<(usize, &str)> {
+        self.excerpt.iter()
+            .find(|line| line.line == lno)
+            .map(|line| (line.start, line.text.as_str()))
+    }
+
+    /// Render the errors. At most `OWNED_CONTEXT` lines of context are
+    /// kept around each span.
+    pub fn render_with(&self, config: &RenderConfig) -> String {
+        let line_at = |lno: usize| self.line_at(lno);
+        self.internal.iter()
+            .map(|err| render(err, &line_at, config))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Every error as JSON, one per line. See `Error::to_json`.
+    pub fn to_json_lines(&self, file: Option<&str>) -> String {
+        let line_at = |lno: usize| self.line_at(lno);
+        self.internal.iter()
+            .map(|err| format!("{}\n", to_json(err, file, &line_at)))
+            .collect::<String>()
+    }
+}
+
+impl From<JaclError<'_>> for OwnedJaclError {
+    fn from(err: JaclError<'_>) -> OwnedJaclError {
+        err.into_owned()
+    }
+}
+
+impl fmt::Display for OwnedJaclError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render().trim_end())
+    }
+}
+
+impl std::error::Error for OwnedJaclError {}
+
 fn transform_entry<'s, 'jacl: 's>(entry: &'s Option<Struct>, jacl: &'jacl Jacl) -> Option<JaclStruct<'s>> {
-    match entry {
-        Some(Struct::Object { entries, props }) => {
-            Some(JaclStruct::Object(
-                Object { jacl, entries, props }
-            ))
-        },
-        Some(Struct::Table { entries }) => {
-            Some(JaclStruct::Table(
-                Table { jacl, entries }
-            ))
-        },
-        Some(Struct::Map { props }) => {
-            Some(JaclStruct::Map(
-                Map { jacl, props }
-            ))
-        }
-        None => {
-            None
-        }
+    entry.as_ref().map(|strct| view(strct, jacl))
+}
+
+/// A view of `strct`, which belongs to `jacl`
+pub(crate) fn view<'s>(strct: &'s Struct, jacl: &'s Jacl) -> JaclStruct<'s> {
+    match strct {
+        Struct::Object { entries, props, meta } => JaclStruct::Object(Object { jacl, inr: strct, entries, props, meta }),
+        Struct::Table { entries, meta } => JaclStruct::Table(Table { jacl, inr: strct, entries, meta }),
+        Struct::Map { props, meta } => JaclStruct::Map(Map { jacl, inr: strct, props, meta }),
     }
 }
 
-fn transform_entries<'s, 'jacl: 's>(entries: &'s Entries, jacl: &'jacl Jacl) -> Vec<(Option<&'s String>, Option<JaclStruct<'s>>)> {
-    entries.iter()
-        .map(|(key, entry)| {
-            let strct = transform_entry(entry, jacl);
-            if key.starts_with('#') {
-               (None, strct)
-            }
-            else {
-               (Some(key), strct)
-            }
-        }).collect::<Vec<(Option<&String>, Option<JaclStruct>)>>()
+/// The entries of a Table or Object, borrowed from the document
+#[derive(Clone, Copy, Debug)]
+pub struct EntryList<'s> {
+    jacl: &'s Jacl,
+    entries: &'s Entries,
+}
+
+fn entry_item<'s>(key: &'s str, entry: &'s Option<Struct>, jacl: &'s Jacl) -> (Option<&'s str>, Option<JaclStruct<'s>>) {
+    let key = if key.starts_with('#') { None } else { Some(key) };
+    (key, transform_entry(entry, jacl))
+}
+
+impl<'s> EntryList<'s> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Is there a named entry with this key?
+    pub fn contains_key(&self, key: &str) -> bool {
+        !key.starts_with('#') && self.entries.contains_key(key)
+    }
+
+    /// The entry at `idx`, counting anonymous entries
+    pub fn get_index(&self, idx: usize) -> Option<(Option<&'s str>, Option<JaclStruct<'s>>)> {
+        self.entries.get_index(idx).map(|(key, entry)| entry_item(key, entry, self.jacl))
+    }
+
+    /// The position of the entry with this key
+    pub fn index_of(&self, key: &str) -> Option<usize> {
+        if key.starts_with('#') { None } else { self.entries.get_index_of(key) }
+    }
+
+    pub fn first(&self) -> Option<(Option<&'s str>, Option<JaclStruct<'s>>)> {
+        self.get_index(0)
+    }
+
+    pub fn last(&self) -> Option<(Option<&'s str>, Option<JaclStruct<'s>>)> {
+        self.len().checked_sub(1).and_then(|idx| self.get_index(idx))
+    }
+
+    pub fn iter(&self) -> EntryIter<'s> {
+        EntryIter { jacl: self.jacl, inner: self.entries.iter() }
+    }
+}
+
+impl<'s> IntoIterator for EntryList<'s> {
+    type Item = (Option<&'s str>, Option<JaclStruct<'s>>);
+    type IntoIter = EntryIter<'s>;
+
+    fn into_iter(self) -> EntryIter<'s> {
+        self.iter()
+    }
+}
+
+/// Each entry in order, with no key if it is anonymous
+#[derive(Clone, Debug)]
+pub struct EntryIter<'s> {
+    jacl: &'s Jacl,
+    inner: indexmap::map::Iter<'s, String, Option<Struct>>,
+}
+
+impl<'s> Iterator for EntryIter<'s> {
+    type Item = (Option<&'s str>, Option<JaclStruct<'s>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let jacl = self.jacl;
+        self.inner.next().map(|(key, entry)| entry_item(key, entry, jacl))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let jacl = self.jacl;
+        self.inner.nth(n).map(|(key, entry)| entry_item(key, entry, jacl))
+    }
+}
+
+impl DoubleEndedIterator for EntryIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let jacl = self.jacl;
+        self.inner.next_back().map(|(key, entry)| entry_item(key, entry, jacl))
+    }
+}
+
+impl ExactSizeIterator for EntryIter<'_> {}
+
+impl FusedIterator for EntryIter<'_> {}
+
+/// The properties of an Object or Map, borrowed from the document
+#[derive(Clone, Copy, Debug)]
+pub struct PropertyList<'s> {
+    props: &'s Props,
+}
+
+impl<'s> PropertyList<'s> {
+    pub fn len(&self) -> usize {
+        self.props.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.props.is_empty()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.props.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'s Value> {
+        self.props.get(name)
+    }
+
+    pub fn get_index(&self, idx: usize) -> Option<(&'s str, &'s Value)> {
+        self.props.get_index(idx).map(|(name, val)| (name.as_str(), val))
+    }
+
+    /// The position of the property with this name
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.props.get_index_of(name)
+    }
+
+    pub fn first(&self) -> Option<(&'s str, &'s Value)> {
+        self.get_index(0)
+    }
+
+    pub fn last(&self) -> Option<(&'s str, &'s Value)> {
+        self.len().checked_sub(1).and_then(|idx| self.get_index(idx))
+    }
+
+    pub fn iter(&self) -> PropertyIter<'s> {
+        PropertyIter { inner: self.props.iter() }
+    }
+}
+
+impl<'s> IntoIterator for PropertyList<'s> {
+    type Item = (&'s str, &'s Value);
+    type IntoIter = PropertyIter<'s>;
+
+    fn into_iter(self) -> PropertyIter<'s> {
+        self.iter()
+    }
 }
 
+/// Each property in the order it was first bound
+#[derive(Clone, Debug)]
+pub struct PropertyIter<'s> {
+    inner: indexmap::map::Iter<'s, String, Value>,
+}
+
+impl<'s> Iterator for PropertyIter<'s> {
+    type Item = (&'s str, &'s Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(name, val)| (name.as_str(), val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth(n).map(|(name, val)| (name.as_str(), val))
+    }
+}
+
+impl DoubleEndedIterator for PropertyIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(name, val)| (name.as_str(), val))
+    }
+}
+
+impl ExactSizeIterator for PropertyIter<'_> {}
+
+impl FusedIterator for PropertyIter<'_> {}
+
 pub trait EntryStruct<'s> {
-    fn entries(&self) -> Vec<(Option<&String>, Option<JaclStruct<'s>>)>;
+    fn entries(&self) -> EntryList<'s>;
     fn get_entry(&self, key: &str) -> Option<JaclStruct<'s>>;
     fn resolve_key(&self, key: &Value) -> Option<JaclStruct<'s>>;
+
+    /// Where the entry was first defined - its key, or its structure if anonymous
+    fn entry_span(&self, key: &str) -> Option<Span>;
+
+    /// Every definition which contributed to the entry, in order
+    fn entry_definitions(&self, key: &str) -> Option<&[Span]>;
+
+    /// Whether the entry was written in the document or filled in from
+    /// a schema's default
+    fn entry_origin(&self, key: &str) -> Option<Origin>;
+}
+
+pub trait PropertyStruct<'s> {
+    fn properties(&self) -> PropertyList<'s>;
+    fn get_property(&self, val: &str) -> Option<&'s Value>;
+
+    /// Where the current value of the property is defined
+    fn property_span(&self, val: &str) -> Option<Span>;
+
+    /// Where the var of the property is bound
+    fn property_name_span(&self, val: &str) -> Option<Span>;
+
+    /// Where the current value of the property, and each of its
+    /// elements if it is a Tuple, are defined
+    fn value_span(&self, val: &str) -> Option<&ValueSpan>;
+
+    /// Where the current value of the property came from, and which
+    /// earlier definitions it overrode. Properties filled in from a
+    /// schema's default have none.
+    fn provenance(&self, val: &str) -> Option<Provenance<'_>>;
+
+    /// Whether the property was written in the document or filled in from
+    /// a schema's default
+    fn property_origin(&self, val: &str) -> Option<Origin>;
 }
 
-pub trait PropertyStruct {
-    fn properties(&self) -> Vec<(&String, &Value)>;
-    fn get_property(&self, val: &str) -> Option<&Value>;
+/// Where an entry or property came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// Written in the document, or added to it in code
+    Document,
+    /// Filled in from a default given by a schema
+    Default,
+}
+
+/// The origin of the current value of a property
+#[derive(Debug)]
+pub struct Provenance<'s> {
+    /// Where the var was bound to the current value
+    pub var: Span,
+    /// Where the current value is defined
+    pub value: &'s ValueSpan,
+    /// Earlier definitions replaced by later ones, oldest first
+    pub overridden: &'s [Definition],
+}
+
+fn entry_origin(meta: &Meta, entries: &Entries, key: &str) -> Option<Origin> {
+    entries.get(key)?;
+    Some(if meta.default_entries.contains(key) { Origin::Default } else { Origin::Document })
+}
+
+fn property_origin(meta: &Meta, props: &Props, val: &str) -> Option<Origin> {
+    props.get(val)?;
+    Some(if meta.default_props.contains(val) { Origin::Default } else { Origin::Document })
+}
+
+fn entry_span(meta: &Meta, key: &str) -> Option<Span> {
+    meta.entries.get(key).and_then(|spans| spans.first()).copied()
+}
+
+fn entry_definitions<'s>(meta: &'s Meta, key: &str) -> Option<&'s [Span]> {
+    meta.entries.get(key).map(|spans| spans.as_slice())
+}
+
+fn property_span(meta: &Meta, val: &str) -> Option<Span> {
+    meta.props.get(val).map(|prop| prop.value.span)
+}
+
+fn property_name_span(meta: &Meta, val: &str) -> Option<Span> {
+    meta.props.get(val).map(|prop| prop.name)
+}
+
+fn value_span<'s>(meta: &'s Meta, val: &str) -> Option<&'s ValueSpan> {
+    meta.props.get(val).map(|prop| &prop.value)
+}
+
+fn provenance<'s>(meta: &'s Meta, val: &str) -> Option<Provenance<'s>> {
+    meta.props.get(val).map(|prop| {
+        Provenance {
+            var: prop.name,
+            value: &prop.value,
+            overridden: &prop.history,
+        }
+    })
 }
 
 #[derive(Debug)]
 pub struct Object<'s> {
     jacl: &'s Jacl,
+    inr: &'s Struct,
     entries: &'s Entries,
     props: &'s Props,
+    meta: &'s Meta,
 }
 
 impl<'s> EntryStruct<'s> for Object<'s> {
-    fn entries(&self) -> Vec<(Option<&String>, Option<JaclStruct<'s>>)> {
-        transform_entries(self.entries, self.jacl)
+    fn entries(&self) -> EntryList<'s> {
+        EntryList { jacl: self.jacl, entries: self.entries }
     }
 
     fn get_entry(&self, key: &str) -> Option<JaclStruct<'s>> {
@@ -95,25 +481,61 @@ impl<'s> EntryStruct<'s> for Object<'s> {
 
     fn resolve_key(&self, key: &Value) -> Option<JaclStruct<'s>> {
         if let Value::Key(key) = key {
-            self.get_entry(&key)
+            self.get_entry(key)
         }
         else {
             None
         }
     }
+    fn entry_span(&self, key: &str) -> Option<Span> {
+        entry_span(self.meta, key)
+    }
+
+    fn entry_definitions(&self, key: &str) -> Option<&[Span]> {
+        entry_definitions(self.meta, key)
+    }
+
+    fn entry_origin(&self, key: &str) -> Option<Origin> {
+        entry_origin(self.meta, self.entries, key)
+    }
 }
 
-impl PropertyStruct for Object<'_> {
-    fn properties(&self) -> Vec<(&String, &Value)> {
-        self.props.iter().collect::<Vec<(&String, &Value)>>()
+impl<'s> PropertyStruct<'s> for Object<'s> {
+    fn properties(&self) -> PropertyList<'s> {
+        PropertyList { props: self.props }
     }
 
-    fn get_property(&self, val: &str) -> Option<&Value> {
+    fn get_property(&self, val: &str) -> Option<&'s Value> {
         self.props.get(val)
     }
+    fn property_span(&self, val: &str) -> Option<Span> {
+        property_span(self.meta, val)
+    }
+
+    fn property_name_span(&self, val: &str) -> Option<Span> {
+        property_name_span(self.meta, val)
+    }
+
+    fn value_span(&self, val: &str) -> Option<&ValueSpan> {
+        value_span(self.meta, val)
+    }
+
+    fn provenance(&self, val: &str) -> Option<Provenance<'_>> {
+        provenance(self.meta, val)
+    }
+
+    fn property_origin(&self, val: &str) -> Option<Origin> {
+        property_origin(self.meta, self.props, val)
+    }
 }
 
 impl<'s> Object<'s> {
+    /// Where this Object is defined, from its opening to its closing brace.
+    /// The root Object has no Span.
+    pub fn span(&self) -> Option<Span> {
+        self.meta.span
+    }
+
     pub fn resolve_property<S: AsRef<str>>(&self, val: S) -> Option<JaclStruct<'s>> {
         match self.props.get(val.as_ref()) {
             Some(key@Value::Key(..)) => self.resolve_key(key),
@@ -126,12 +548,14 @@ impl<'s> Object<'s> {
 #[derive(Debug)]
 pub struct Table<'s> {
     jacl: &'s Jacl,
+    inr: &'s Struct,
     entries: &'s Entries,
+    meta: &'s Meta,
 }
 
 impl<'s> EntryStruct<'s> for Table<'s> {
-    fn entries(&self) -> Vec<(Option<&String>, Option<JaclStruct<'s>>)> {
-        transform_entries(self.entries, self.jacl)
+    fn entries(&self) -> EntryList<'s> {
+        EntryList { jacl: self.jacl, entries: self.entries }
     }
 
     fn get_entry(&self, key: &str) -> Option<JaclStruct<'s>> {
@@ -143,28 +567,74 @@ impl<'s> EntryStruct<'s> for Table<'s> {
 
     fn resolve_key(&self, key: &Value) -> Option<JaclStruct<'s>> {
         if let Value::Key(key) = key {
-            self.get_entry(&key)
+            self.get_entry(key)
         }
         else {
             None
         }
     }
+    fn entry_span(&self, key: &str) -> Option<Span> {
+        entry_span(self.meta, key)
+    }
+
+    fn entry_definitions(&self, key: &str) -> Option<&[Span]> {
+        entry_definitions(self.meta, key)
+    }
+
+    fn entry_origin(&self, key: &str) -> Option<Origin> {
+        entry_origin(self.meta, self.entries, key)
+    }
+}
+
+impl Table<'_> {
+    /// Where this Table is defined, from its opening to its closing bracket
+    pub fn span(&self) -> Option<Span> {
+        self.meta.span
+    }
 }
 
 #[derive(Debug)]
 pub struct Map<'s> {
     jacl: &'s Jacl,
+    inr: &'s Struct,
     props: &'s Props,
+    meta: &'s Meta,
 }
 
-impl PropertyStruct for Map<'_> {
-    fn properties(&self) -> Vec<(&String, &Value)> {
-        self.props.iter().collect::<Vec<(&String, &Value)>>()
+impl<'s> PropertyStruct<'s> for Map<'s> {
+    fn properties(&self) -> PropertyList<'s> {
+        PropertyList { props: self.props }
     }
 
-    fn get_property(&self, val: &str) -> Option<&Value> {
+    fn get_property(&self, val: &str) -> Option<&'s Value> {
         self.props.get(val)
     }
+    fn property_span(&self, val: &str) -> Option<Span> {
+        property_span(self.meta, val)
+    }
+
+    fn property_name_span(&self, val: &str) -> Option<Span> {
+        property_name_span(self.meta, val)
+    }
+
+    fn value_span(&self, val: &str) -> Option<&ValueSpan> {
+        value_span(self.meta, val)
+    }
+
+    fn provenance(&self, val: &str) -> Option<Provenance<'_>> {
+        provenance(self.meta, val)
+    }
+
+    fn property_origin(&self, val: &str) -> Option<Origin> {
+        property_origin(self.meta, self.props, val)
+    }
+}
+
+impl Map<'_> {
+    /// Where this Map is defined, from its opening to its closing brace
+    pub fn span(&self) -> Option<Span> {
+        self.meta.span
+    }
 }
 
 #[derive(Debug)]
@@ -175,7 +645,32 @@ pub enum JaclStruct<'s> {
 }
 
 impl<'s> JaclStruct<'s> {
-    pub fn as_entry_struct(&self) -> Option<&dyn EntryStruct> {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            JaclStruct::Object(strct) => strct.span(),
+            JaclStruct::Table(strct) => strct.span(),
+            JaclStruct::Map(strct) => strct.span(),
+        }
+    }
+
+    /// The document this structure belongs to
+    pub(crate) fn jacl(&self) -> &'s Jacl {
+        match self {
+            JaclStruct::Object(strct) => strct.jacl,
+            JaclStruct::Table(strct) => strct.jacl,
+            JaclStruct::Map(strct) => strct.jacl,
+        }
+    }
+
+    pub(crate) fn data(&self) -> &'s Struct {
+        match self {
+            JaclStruct::Object(strct) => strct.inr,
+            JaclStruct::Table(strct) => strct.inr,
+            JaclStruct::Map(strct) => strct.inr,
+        }
+    }
+
+    pub fn as_entry_struct(&self) -> Option<&dyn EntryStruct<'s>> {
         match self {
             JaclStruct::Map(_) => None,
             JaclStruct::Object(strct) => Some(strct as &dyn EntryStruct),
@@ -183,10 +678,10 @@ impl<'s> JaclStruct<'s> {
         }
     }
 
-    pub fn as_property_struct(&self) -> Option<&dyn PropertyStruct> {
+    pub fn as_property_struct(&self) -> Option<&dyn PropertyStruct<'s>> {
         match self {
-            JaclStruct::Map(strct) => Some(strct as &dyn PropertyStruct),
-            JaclStruct::Object(strct) => Some(strct as &dyn PropertyStruct),
+            JaclStruct::Map(strct) => Some(strct as &dyn PropertyStruct<'s>),
+            JaclStruct::Object(strct) => Some(strct as &dyn PropertyStruct<'s>),
             JaclStruct::Table(_) => None,
         }
     }
@@ -194,26 +689,77 @@ impl<'s> JaclStruct<'s> {
 
 #[derive(Debug)]
 pub struct Jacl {
-    inr: Struct 
+    inr: Struct,
+    source: String,
+    lines: Lines,
 }
 
 impl Jacl {
-    pub fn init(data: Struct) -> Jacl {
+    pub(crate) fn init(data: Struct, source: &str, lines: Lines) -> Jacl {
         Jacl {
             inr: data,
+            source: source.to_string(),
+            lines,
         }
     }
 
+    /// Make a document from `root`, which must be an Object. The
+    /// document has no source text, so nothing within it has a Span.
+    pub fn from_structure(root: &Structure) -> Result<Jacl, DomError> {
+        if root.kind() != StructKind::Object {
+            return Err(DomError::RootNotObject(root.kind()));
+        }
+        Ok(Jacl::init(root.to_struct(), "", vec![(0, 0)]))
+    }
+
+    /// Copy the document into a Structure which can be changed
+    pub fn to_structure(&self) -> Structure {
+        Structure::from(self)
+    }
+
+    pub(crate) fn data(&self) -> &Struct {
+        &self.inr
+    }
+
+    pub(crate) fn data_mut(&mut self) -> &mut Struct {
+        &mut self.inr
+    }
+
+    pub(crate) fn lines(&self) -> &Lines {
+        &self.lines
+    }
+
     pub fn root<'s, 'jacl: 's>(&'jacl self) -> Object<'s> {
-        if let Struct::Object { entries, props } = &self.inr {
+        if let Struct::Object { entries, props, meta } = &self.inr {
             Object {
-                jacl: &self,
-                entries: &entries,
-                props: &props,
+                jacl: self,
+                inr: &self.inr,
+                entries,
+                props,
+                meta,
             }
         }
         else {
             panic!();
         }
     }
+
+    /// Build a diagnostic pointing at `span` in the source of this Jacl,
+    /// for reporting problems the application finds in the data.
+    pub fn error_at<M: Into<String>>(&self, span: Span, message: M, hint: Option<&str>) -> JaclError<'_> {
+        let err = Error::custom(message.into(), span, hint.map(String::from));
+        JaclError::from_error(err, &self.source, self.lines.clone())
+    }
+
+    /// Report problems found in the data, located in the source of this Jacl
+    pub(crate) fn error_from(&self, errs: Vec<Error>) -> JaclError<'_> {
+        JaclError::from_errors(errs, &self.source, self.lines.clone())
+    }
+}
+
+/// Documents are equal if they hold the same data, however it was written
+impl PartialEq for Jacl {
+    fn eq(&self, other: &Jacl) -> bool {
+        self.inr == other.inr
+    }
 }
@@ -0,0 +1,632 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::{Lines, Jacl, JaclError};
+use crate::error::Error;
+use crate::tokeniser::{self, Token, TokVal};
+
+/* Concrete Syntax Tree
+ *
+ * The CST covers every byte of its input, including whitespace and
+ * comments, so that tools can inspect and rewrite a document without
+ * disturbing its layout. It is built from the same Tokens as the parser
+ * uses, with the gaps between them kept as trivia, and is lowered by
+ * feeding its Tokens back through the parser.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxKind {
+    // Tokens
+    Name,
+    String,
+    Integer,
+    Float,
+    Boolean,
+
+    LBrace,
+    RBrace,
+    LBrack,
+    RBrack,
+    LBracePct,
+    RBracePct,
+    LParen,
+    RParen,
+
+    Equals,
+    Comma,
+    Plus,
+    Minus,
+    Star,
+    Dollar,
+    At,
+
+    Break,
+
+    // Trivia
+    Whitespace,
+    Comment,
+    Garbage,
+
+    // Nodes
+    Root,
+    Object,
+    Table,
+    Map,
+    Binding,
+    Entry,
+    Value,
+    Var,
+    Tuple,
+    Error,
+}
+
+impl SyntaxKind {
+    /// Trivia carries no meaning - whitespace, comments, and text
+    /// which could not be tokenised
+    pub fn is_trivia(self) -> bool {
+        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment | SyntaxKind::Garbage)
+    }
+
+    pub fn is_struct(self) -> bool {
+        matches!(self, SyntaxKind::Object | SyntaxKind::Table | SyntaxKind::Map)
+    }
+
+    fn is_open(self) -> bool {
+        matches!(self, SyntaxKind::LBrace | SyntaxKind::LBrack | SyntaxKind::LBracePct)
+    }
+
+    fn is_close(self) -> bool {
+        matches!(self, SyntaxKind::RBrace | SyntaxKind::RBrack | SyntaxKind::RBracePct)
+    }
+
+    fn is_literal(self) -> bool {
+        matches!(self, SyntaxKind::Name | SyntaxKind::String | SyntaxKind::Integer |
+                       SyntaxKind::Float | SyntaxKind::Boolean)
+    }
+
+    fn from_tokval(val: &TokVal) -> SyntaxKind {
+        match val {
+            TokVal::Name(_) => SyntaxKind::Name,
+            TokVal::String(_) => SyntaxKind::String,
+            TokVal::Integer(_) => SyntaxKind::Integer,
+            TokVal::Float(_) => SyntaxKind::Float,
+            TokVal::Boolean(_) => SyntaxKind::Boolean,
+            TokVal::LBrace => SyntaxKind::LBrace,
+            TokVal::RBrace => SyntaxKind::RBrace,
+            TokVal::LBrack => SyntaxKind::LBrack,
+            TokVal::RBrack => SyntaxKind::RBrack,
+            TokVal::LBracePct => SyntaxKind::LBracePct,
+            TokVal::RBracePct => SyntaxKind::RBracePct,
+            TokVal::LParen => SyntaxKind::LParen,
+            TokVal::RParen => SyntaxKind::RParen,
+            TokVal::Equals => SyntaxKind::Equals,
+            TokVal::Comma => SyntaxKind::Comma,
+            TokVal::Plus => SyntaxKind::Plus,
+            TokVal::Minus => SyntaxKind::Minus,
+            TokVal::Star => SyntaxKind::Star,
+            TokVal::Dollar => SyntaxKind::Dollar,
+            TokVal::At => SyntaxKind::At,
+            TokVal::Break => SyntaxKind::Break,
+            TokVal::Fault => SyntaxKind::Garbage,
+        }
+    }
+
+    /// Rebuild the TokVal of a token of this kind from its text
+    fn to_tokval(self, text: &str) -> Option<TokVal<'_>> {
+        match self {
+            SyntaxKind::Name => Some(TokVal::Name(text)),
+            SyntaxKind::String => Some(TokVal::String(&text[1..text.len() - 1])),
+            SyntaxKind::Integer => text.parse::<i64>().ok().map(TokVal::Integer),
+            SyntaxKind::Float => text.parse::<f64>().ok().map(TokVal::Float),
+            SyntaxKind::Boolean => Some(TokVal::Boolean(text == "true")),
+            SyntaxKind::LBrace => Some(TokVal::LBrace),
+            SyntaxKind::RBrace => Some(TokVal::RBrace),
+            SyntaxKind::LBrack => Some(TokVal::LBrack),
+            SyntaxKind::RBrack => Some(TokVal::RBrack),
+            SyntaxKind::LBracePct => Some(TokVal::LBracePct),
+            SyntaxKind::RBracePct => Some(TokVal::RBracePct),
+            SyntaxKind::LParen => Some(TokVal::LParen),
+            SyntaxKind::RParen => Some(TokVal::RParen),
+            SyntaxKind::Equals => Some(TokVal::Equals),
+            SyntaxKind::Comma => Some(TokVal::Comma),
+            SyntaxKind::Plus => Some(TokVal::Plus),
+            SyntaxKind::Minus => Some(TokVal::Minus),
+            SyntaxKind::Star => Some(TokVal::Star),
+            SyntaxKind::Dollar => Some(TokVal::Dollar),
+            SyntaxKind::At => Some(TokVal::At),
+            SyntaxKind::Break => Some(TokVal::Break),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxToken {
+    kind: SyntaxKind,
+    range: Range<usize>,
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// Byte range of the token within the source
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn text<'t>(&self, tree: &'t SyntaxTree) -> &'t str {
+        &tree.source[self.range.clone()]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxNode {
+    kind: SyntaxKind,
+    range: Range<usize>,
+    children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    fn new(kind: SyntaxKind, children: Vec<SyntaxElement>, at: usize) -> SyntaxNode {
+        let start = children.first().map(|child| child.range().start).unwrap_or(at);
+        let end = children.last().map(|child| child.range().end).unwrap_or(at);
+        SyntaxNode {
+            kind,
+            range: start..end,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// Byte range of the node within the source
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn children(&self) -> &[SyntaxElement] {
+        &self.children
+    }
+
+    /// The child nodes of this node, skipping its tokens
+    pub fn child_nodes(&self) -> Vec<&SyntaxNode> {
+        self.children.iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            }).collect::<Vec<&SyntaxNode>>()
+    }
+
+    /// Every token beneath this node, in source order
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut toks = Vec::new();
+        self.collect_tokens(&mut toks);
+        toks
+    }
+
+    fn collect_tokens<'t>(&'t self, toks: &mut Vec<&'t SyntaxToken>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(toks),
+                SyntaxElement::Token(tok) => toks.push(tok),
+            }
+        }
+    }
+
+    pub fn text<'t>(&self, tree: &'t SyntaxTree) -> &'t str {
+        &tree.source[self.range.clone()]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            SyntaxElement::Node(node) => node.kind,
+            SyntaxElement::Token(tok) => tok.kind,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        match self {
+            SyntaxElement::Node(node) => node.range(),
+            SyntaxElement::Token(tok) => tok.range(),
+        }
+    }
+}
+
+/// A lossless syntax tree over a JACL document
+#[derive(Clone, Debug)]
+pub struct SyntaxTree {
+    source: String,
+    lines: Lines,
+    root: SyntaxNode,
+    errors: Vec<Error>,
+}
+
+impl SyntaxTree {
+    /// Build the syntax tree of `input`. This never fails - text which
+    /// cannot be understood is kept in Error nodes and Garbage tokens.
+    pub fn parse(input: &str) -> SyntaxTree {
+        let (lines, toks, errors) = tokeniser::tokenise(input);
+        let leaves = leaves(input, &toks);
+        let mut builder = Builder {
+            leaves,
+            pos: 0,
+        };
+        let root = builder.root();
+        SyntaxTree {
+            source: input.to_string(),
+            lines,
+            root,
+            errors,
+        }
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// The text the tree was built from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Reassemble the document from the tokens of the tree.
+    /// This is always identical to the source.
+    pub fn text(&self) -> String {
+        self.root.tokens().iter()
+            .map(|tok| tok.text(self))
+            .collect::<String>()
+    }
+
+    /// Replace the text in `range` and rebuild the tree
+    pub(crate) fn splice(&mut self, range: Range<usize>, text: &str) {
+        let mut source = self.source.clone();
+        source.replace_range(range, text);
+        *self = SyntaxTree::parse(&source);
+    }
+
+    /// Lower the tree into a Jacl, as `read_string` would
+    pub fn lower(&self) -> Result<Jacl, JaclError<'_>> {
+        match self.lower_partial() {
+            (jacl, None) => Ok(jacl),
+            (_, Some(err)) => Err(err),
+        }
+    }
+
+    /// Lower the tree into a Jacl, as `read_string_partial` would
+    pub fn lower_partial(&self) -> (Jacl, Option<JaclError<'_>>) {
+        let mut toks = Vec::new();
+        for leaf in self.root.tokens() {
+            let text = leaf.text(self);
+            if let Some(val) = leaf.kind.to_tokval(text) {
+                let (lno, col) = self.position(leaf.range.start);
+                toks.push(Token::new(val, leaf.range.start, leaf.range.end,
+                                     lno, col, text.chars().count()));
+            }
+        }
+
+        // The tokeniser always finishes with a Break
+        let (lno, col) = self.position(self.source.len());
+        toks.push(Token::new(TokVal::Break, self.source.len(), self.source.len(),
+                             lno, col, 1));
+
+        crate::build(&self.source, self.lines.clone(), toks, self.errors.clone())
+    }
+
+    /// Line and column of the byte at `offset`, counting from 1
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let idx = self.lines.partition_point(|line| line.0 <= offset).saturating_sub(1);
+        let line_start = self.lines.get(idx).map(|line| line.0).unwrap_or(0);
+        (idx + 1, self.source[line_start..offset].chars().count() + 1)
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/* Build Leaves */
+
+/// Interleave the Tokens with trivia covering the gaps between them
+fn leaves(input: &str, toks: &[Token]) -> Vec<SyntaxToken> {
+    let mut leaves = Vec::new();
+    let mut pos = 0;
+    for tok in toks {
+        let span = tok.span();
+        if span.start > pos {
+            trivia(input, pos, span.start, &mut leaves);
+        }
+        if span.end > span.start {
+            leaves.push(SyntaxToken {
+                kind: SyntaxKind::from_tokval(&tok.val),
+                range: span.start..span.end,
+            });
+        }
+        pos = usize::max(pos, span.end);
+    }
+    if pos < input.len() {
+        trivia(input, pos, input.len(), &mut leaves);
+    }
+    leaves
+}
+
+/// Split the text between two Tokens into whitespace, comments and garbage.
+/// Newlines are always Tokens, so a comment runs to the end of the gap.
+fn trivia(input: &str, start: usize, end: usize, leaves: &mut Vec<SyntaxToken>) {
+    let gap = &input[start..end];
+    let mut chars = gap.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let (kind, len) = if c == '#' {
+            (SyntaxKind::Comment, gap.len() - offset)
+        }
+        else {
+            let kind = if c.is_whitespace() { SyntaxKind::Whitespace } else { SyntaxKind::Garbage };
+            let mut len = c.len_utf8();
+            while let Some(&(_, nc)) = chars.peek() {
+                if nc == '#' || nc.is_whitespace() != c.is_whitespace() {
+                    break;
+                }
+                len += nc.len_utf8();
+                chars.next();
+            }
+            (kind, len)
+        };
+        leaves.push(SyntaxToken {
+            kind,
+            range: (start + offset)..(start + offset + len),
+        });
+        if kind == SyntaxKind::Comment {
+            break;
+        }
+    }
+}
+
+/* Build Nodes */
+
+struct Builder {
+    leaves: Vec<SyntaxToken>,
+    pos: usize,
+}
+
+impl Builder {
+    /// The position just past the last consumed leaf
+    fn at(&self) -> usize {
+        self.pos.checked_sub(1)
+            .and_then(|pos| self.leaves.get(pos))
+            .map(|leaf| leaf.range.end)
+            .unwrap_or(0)
+    }
+
+    /// Kind of the `n`th significant leaf from the current position
+    fn nth(&self, n: usize) -> Option<SyntaxKind> {
+        self.leaves[self.pos..].iter()
+            .filter(|leaf| !leaf.kind.is_trivia())
+            .nth(n)
+            .map(|leaf| leaf.kind)
+    }
+
+    fn cur(&self) -> Option<SyntaxKind> {
+        self.nth(0)
+    }
+
+    fn trivia(&mut self, out: &mut Vec<SyntaxElement>) {
+        while let Some(leaf) = self.leaves.get(self.pos) {
+            if !leaf.kind.is_trivia() {
+                break;
+            }
+            out.push(SyntaxElement::Token(leaf.clone()));
+            self.pos += 1;
+        }
+    }
+
+    /// Consume any trivia, then the next significant leaf
+    fn bump(&mut self, out: &mut Vec<SyntaxElement>) {
+        self.trivia(out);
+        if let Some(leaf) = self.leaves.get(self.pos) {
+            out.push(SyntaxElement::Token(leaf.clone()));
+            self.pos += 1;
+        }
+    }
+
+    /// Consume any line breaks, as allowed within a tuple
+    fn breaks(&mut self, out: &mut Vec<SyntaxElement>) {
+        while self.cur() == Some(SyntaxKind::Break) {
+            self.bump(out);
+        }
+    }
+
+    fn root(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        loop {
+            self.block(&mut children);
+            self.trivia(&mut children);
+            if self.cur().is_none() {
+                break;
+            }
+            // A stray closing bracket
+            let mut err = Vec::new();
+            self.bump(&mut err);
+            children.push(SyntaxElement::Node(SyntaxNode::new(SyntaxKind::Error, err, self.at())));
+        }
+        SyntaxNode::new(SyntaxKind::Root, children, 0)
+    }
+
+    fn block(&mut self, out: &mut Vec<SyntaxElement>) {
+        loop {
+            self.trivia(out);
+            match self.cur() {
+                None => return,
+                Some(kind) if kind.is_close() => return,
+                Some(SyntaxKind::Break) => self.bump(out),
+                Some(_) => {
+                    let item = self.item();
+                    out.push(SyntaxElement::Node(item));
+                },
+            }
+        }
+    }
+
+    fn item(&mut self) -> SyntaxNode {
+        match self.cur() {
+            Some(SyntaxKind::Name) => {
+                if matches!(self.nth(1), Some(SyntaxKind::Comma) | Some(SyntaxKind::Equals)) {
+                    self.binding()
+                }
+                else {
+                    self.entry()
+                }
+            },
+            Some(kind) if kind.is_open() => {
+                self.entry()
+            },
+            _ => {
+                self.error()
+            }
+        }
+    }
+
+    fn binding(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        loop {
+            self.bump(&mut children);
+            if self.cur() == Some(SyntaxKind::Comma) && self.nth(1) == Some(SyntaxKind::Name) {
+                self.bump(&mut children);
+            }
+            else {
+                break;
+            }
+        }
+        if self.cur() == Some(SyntaxKind::Equals) {
+            self.bump(&mut children);
+            match self.cur() {
+                Some(kind) if kind.is_open() => {
+                    self.trivia(&mut children);
+                    let strct = self.strct();
+                    children.push(SyntaxElement::Node(strct));
+                },
+                Some(kind) if kind.is_literal() ||
+                              kind == SyntaxKind::Dollar || kind == SyntaxKind::LParen => {
+                    self.trivia(&mut children);
+                    let value = self.value();
+                    children.push(SyntaxElement::Node(value));
+                },
+                _ => {},
+            }
+        }
+        SyntaxNode::new(SyntaxKind::Binding, children, self.at())
+    }
+
+    fn entry(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        if self.cur() == Some(SyntaxKind::Name) {
+            self.bump(&mut children);
+            while self.cur() == Some(SyntaxKind::Plus) && self.nth(1) == Some(SyntaxKind::Name) {
+                self.bump(&mut children);
+                self.bump(&mut children);
+            }
+        }
+        if let Some(kind) = self.cur() {
+            if kind.is_open() {
+                self.trivia(&mut children);
+                let strct = self.strct();
+                children.push(SyntaxElement::Node(strct));
+            }
+        }
+        SyntaxNode::new(SyntaxKind::Entry, children, self.at())
+    }
+
+    fn strct(&mut self) -> SyntaxNode {
+        let (kind, close) = match self.cur() {
+            Some(SyntaxKind::LBrace) => (SyntaxKind::Object, SyntaxKind::RBrace),
+            Some(SyntaxKind::LBrack) => (SyntaxKind::Table, SyntaxKind::RBrack),
+            _ => (SyntaxKind::Map, SyntaxKind::RBracePct),
+        };
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.block(&mut children);
+        if self.cur() == Some(close) {
+            self.bump(&mut children);
+        }
+        SyntaxNode::new(kind, children, self.at())
+    }
+
+    fn value(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        match self.cur() {
+            Some(SyntaxKind::Dollar) => {
+                self.bump(&mut children);
+                if self.cur() == Some(SyntaxKind::Name) {
+                    self.bump(&mut children);
+                }
+                SyntaxNode::new(SyntaxKind::Var, children, self.at())
+            },
+            Some(SyntaxKind::LParen) => {
+                self.bump(&mut children);
+                loop {
+                    self.breaks(&mut children);
+                    match self.cur() {
+                        Some(kind) if kind.is_literal() ||
+                                      kind == SyntaxKind::Dollar || kind == SyntaxKind::LParen => {
+                            self.trivia(&mut children);
+                            let value = self.value();
+                            children.push(SyntaxElement::Node(value));
+                        },
+                        _ => break,
+                    }
+                    self.breaks(&mut children);
+                    if self.cur() == Some(SyntaxKind::Comma) {
+                        self.bump(&mut children);
+                    }
+                    else {
+                        break;
+                    }
+                }
+                if self.cur() == Some(SyntaxKind::RParen) {
+                    self.bump(&mut children);
+                }
+                SyntaxNode::new(SyntaxKind::Tuple, children, self.at())
+            },
+            _ => {
+                self.bump(&mut children);
+                SyntaxNode::new(SyntaxKind::Value, children, self.at())
+            }
+        }
+    }
+
+    /// Consume everything up to the end of the line, or the bracket
+    /// closing the structure we are in
+    fn error(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        let mut depth: usize = 0;
+        self.bump(&mut children);
+        while let Some(kind) = self.cur() {
+            if kind.is_open() {
+                depth += 1;
+            }
+            else if kind.is_close() {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            else if kind == SyntaxKind::Break && depth == 0 {
+                break;
+            }
+            self.bump(&mut children);
+        }
+        SyntaxNode::new(SyntaxKind::Error, children, self.at())
+    }
+}
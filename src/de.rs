@@ -0,0 +1,529 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::{Error, Jacl};
+use crate::path::{format_path, Segment};
+use crate::types::{Value, ValueSpan, Span, Struct};
+use crate::util::did_you_mean;
+
+/* Serde Deserializer
+ *
+ * Objects and Maps deserialize as maps or structs, and Tables as
+ * sequences of their entries, or as maps if their entries are named.
+ * A Key is followed to the entry it names where a structure is wanted,
+ * and is otherwise read as a string, or as the name of an enum variant.
+ */
+
+/// An error from deserializing a document, with where it was found
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeError {
+    message: String,
+    path: Vec<Segment>,
+    span: Option<Span>,
+    /// Every error found reading the text, when it could not be read
+    syntax: Vec<Error>,
+}
+
+impl DeError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The path to the value which could not be deserialized
+    pub fn path(&self) -> &[Segment] {
+        &self.path
+    }
+
+    /// Where the value which could not be deserialized was defined
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// The errors which stopped the text being read as JACL at all. The
+    /// message and span of a DeError are those of the first.
+    pub fn syntax_errors(&self) -> &[Error] {
+        &self.syntax
+    }
+
+    /// Locate the error, unless a deeper value already has
+    fn at(mut self, path: &[Segment], span: Option<Span>) -> DeError {
+        if self.path.is_empty() && self.span.is_none() {
+            self.path = path.to_vec();
+            self.span = span;
+        }
+        self
+    }
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", format_path(&self.path))?;
+        }
+        if let Some(span) = self.span {
+            write!(f, " (line {}, column {})", span.line, span.col)?;
+        }
+        match self.syntax.len() {
+            0 | 1 => {},
+            2 => write!(f, ", and 1 more error")?,
+            n => write!(f, ", and {} more errors", n - 1)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> DeError {
+        DeError {
+            message: msg.to_string(),
+            path: Vec::new(),
+            span: None,
+            syntax: Vec::new(),
+        }
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> DeError {
+        DeError::custom(with_similar(format!("unknown field `{}`", field), field, expected))
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> DeError {
+        DeError::custom(with_similar(format!("unknown variant `{}`", variant), variant, expected))
+    }
+}
+
+/// Add the expected name most like `name` to `message`, or else list them all
+fn with_similar(message: String, name: &str, expected: &'static [&'static str]) -> String {
+    if let Some(similar) = did_you_mean(name, expected.iter().copied()) {
+        return format!("{}, did you mean `{}`?", message, similar);
+    }
+    match expected {
+        [] => format!("{}, there are none", message),
+        _ => format!("{}, expected one of `{}`", message, expected.join("`, `")),
+    }
+}
+
+/// Read a `T` from JACL text
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T, DeError> {
+    let jacl = crate::read_string(input).map_err(|err| {
+        let first = &err.errors()[0];
+        DeError {
+            message: first.message().to_string(),
+            path: Vec::new(),
+            span: first.span(),
+            syntax: err.errors().to_vec(),
+        }
+    })?;
+    from_jacl(&jacl)
+}
+
+/// Read a `T` from a parsed document. Strings may be borrowed from it.
+pub fn from_jacl<'a, T: Deserialize<'a>>(jacl: &'a Jacl) -> Result<T, DeError> {
+    let de = Deserializer {
+        node: Node::Struct(jacl.data()),
+        path: Vec::new(),
+        span: None,
+    };
+    T::deserialize(de)
+}
+
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Struct(&'a Struct),
+    /// An entry without a structure, like `atom`
+    Empty,
+    Value {
+        val: &'a Value,
+        vspan: Option<&'a ValueSpan>,
+        /// The structure the value was bound in, for resolving Keys
+        parent: Option<&'a Struct>,
+    },
+}
+
+struct Deserializer<'a> {
+    node: Node<'a>,
+    path: Vec<Segment>,
+    span: Option<Span>,
+}
+
+fn entries_of(strct: &Struct) -> Option<&crate::types::Entries> {
+    match strct {
+        Struct::Object { entries, .. } | Struct::Table { entries, .. } => Some(entries),
+        Struct::Map { .. } => None,
+    }
+}
+
+fn props_of(strct: &Struct) -> Option<&crate::types::Props> {
+    match strct {
+        Struct::Object { props, .. } | Struct::Map { props, .. } => Some(props),
+        Struct::Table { .. } => None,
+    }
+}
+
+impl<'a> Deserializer<'a> {
+    fn child(&self, seg: Segment, node: Node<'a>, span: Option<Span>) -> Deserializer<'a> {
+        let mut path = self.path.clone();
+        path.push(seg);
+        Deserializer { node, path, span }
+    }
+
+    fn entry(&self, seg: Segment, strct: &'a Struct, key: &str, entry: &'a Option<Struct>) -> Deserializer<'a> {
+        let span = entry.as_ref().and_then(|entry| entry.meta().span)
+                        .or_else(|| strct.meta().entries.get(key).and_then(|spans| spans.first().copied()));
+        let node = match entry {
+            Some(entry) => Node::Struct(entry),
+            None => Node::Empty,
+        };
+        self.child(seg, node, span)
+    }
+
+    /// If this is a Key naming an entry, the entry
+    fn resolve(&self) -> Option<Deserializer<'a>> {
+        if let Node::Value { val: Value::Key(key), parent: Some(parent), .. } = self.node {
+            let entries = entries_of(parent)?;
+            let entry = entries.get(key)?;
+            let span = entry.as_ref().and_then(|entry| entry.meta().span).or(self.span);
+            let node = match entry {
+                Some(entry) => Node::Struct(entry),
+                None => Node::Empty,
+            };
+            return Some(Deserializer { node, path: self.path.clone(), span });
+        }
+        None
+    }
+
+    /// The named items of a structure - its properties, then its entries
+    fn items(&self, strct: &'a Struct) -> Vec<(&'a str, Deserializer<'a>)> {
+        let mut items = Vec::new();
+        if let Some(props) = props_of(strct) {
+            for (name, val) in props {
+                let vspan = strct.meta().props.get(name).map(|prop| &prop.value);
+                let node = Node::Value { val, vspan, parent: Some(strct) };
+                items.push((name.as_str(), self.child(Segment::Key(name.clone()), node, vspan.map(|vs| vs.span))));
+            }
+        }
+        if let Some(entries) = entries_of(strct) {
+            for (key, entry) in entries {
+                if !key.starts_with('#') {
+                    items.push((key.as_str(), self.entry(Segment::Key(key.clone()), strct, key, entry)));
+                }
+            }
+        }
+        items
+    }
+
+    /// The elements of a Tuple, or the entries of a Table or Object
+    fn elems(&self) -> Option<Vec<Deserializer<'a>>> {
+        match self.node {
+            Node::Value { val: Value::Tuple(vals), vspan, parent } => {
+                Some(vals.iter().enumerate().map(|(idx, val)| {
+                    let vspan = vspan.and_then(|vs| vs.elems.get(idx));
+                    let node = Node::Value { val, vspan, parent };
+                    self.child(Segment::Index(idx), node, vspan.map(|vs| vs.span))
+                }).collect())
+            },
+            Node::Struct(strct) => {
+                let entries = entries_of(strct)?;
+                Some(entries.iter().enumerate().map(|(idx, (key, entry))| {
+                    self.entry(Segment::Index(idx), strct, key, entry)
+                }).collect())
+            },
+            _ => None,
+        }
+    }
+
+    /// The value is not of the type wanted. If it is a Key which was
+    /// probably meant to name an entry, say which.
+    fn invalid_type(&self, exp: &dyn de::Expected) -> DeError {
+        let err: DeError = de::Error::invalid_type(self.unexpected(), exp);
+        if let Node::Value { val: Value::Key(key), parent: Some(parent), .. } = self.node {
+            let similar = entries_of(parent).and_then(|entries| did_you_mean(key, entries.keys().map(String::as_str)));
+            if let Some(similar) = similar {
+                return de::Error::custom(format!("{}; the Key `{}` names no entry, did you mean `{}`?", err.message, key, similar));
+            }
+        }
+        err
+    }
+
+    fn unexpected(&self) -> de::Unexpected<'a> {
+        match self.node {
+            Node::Struct(Struct::Object { .. }) => de::Unexpected::Other("Object"),
+            Node::Struct(Struct::Table { .. }) => de::Unexpected::Other("Table"),
+            Node::Struct(Struct::Map { .. }) => de::Unexpected::Other("Map"),
+            Node::Empty => de::Unexpected::Unit,
+            Node::Value { val, .. } => match val {
+                Value::Key(key) => de::Unexpected::Other(if key.starts_with('#') { "anonymous structure" } else { key }),
+                Value::Tuple(_) => de::Unexpected::Seq,
+                Value::String(string) => de::Unexpected::Str(string),
+                Value::Integer(integer) => de::Unexpected::Signed(*integer),
+                Value::Float(float) => de::Unexpected::Float(*float),
+                Value::Boolean(boolean) => de::Unexpected::Bool(*boolean),
+            },
+        }
+    }
+
+    fn locate<T>(&self, result: Result<T, DeError>) -> Result<T, DeError> {
+        result.map_err(|err| err.at(&self.path, self.span))
+    }
+
+    fn visit_any<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if let Some(entry) = self.resolve() {
+            return entry.visit_any(visitor);
+        }
+        let result = match self.node {
+            Node::Struct(Struct::Table { entries, .. }) if entries.keys().all(|key| key.starts_with('#')) => {
+                self.visit_seq(visitor)
+            },
+            Node::Struct(strct) => {
+                let items = self.items(strct);
+                visitor.visit_map(MapAccess { items: items.into_iter(), value: None })
+            },
+            Node::Empty => visitor.visit_unit(),
+            Node::Value { val, .. } => match val {
+                Value::Key(key) | Value::String(key) => visitor.visit_borrowed_str(key),
+                Value::Tuple(_) => self.visit_seq(visitor),
+                Value::Integer(integer) => visitor.visit_i64(*integer),
+                Value::Float(float) => visitor.visit_f64(*float),
+                Value::Boolean(boolean) => visitor.visit_bool(*boolean),
+            },
+        };
+        self.locate(result)
+    }
+
+    fn visit_seq<V: Visitor<'a>>(&self, visitor: V) -> Result<V::Value, DeError> {
+        match self.elems() {
+            Some(elems) => {
+                let len = elems.len();
+                let mut access = SeqAccess { elems: elems.into_iter() };
+                let value = visitor.visit_seq(&mut access)?;
+                if access.elems.next().is_some() {
+                    return Err(de::Error::invalid_length(len, &"fewer elements"));
+                }
+                Ok(value)
+            },
+            None => Err(self.invalid_type(&visitor)),
+        }
+    }
+}
+
+impl<'a> de::Deserializer<'a> for Deserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.visit_any(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if let Some(entry) = self.resolve() {
+            return entry.deserialize_seq(visitor);
+        }
+        let result = self.visit_seq(visitor);
+        self.locate(result)
+    }
+
+    fn deserialize_tuple<V: Visitor<'a>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'a>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if let Some(entry) = self.resolve() {
+            return entry.deserialize_map(visitor);
+        }
+        let result = match self.node {
+            Node::Struct(strct) => {
+                let items = self.items(strct);
+                visitor.visit_map(MapAccess { items: items.into_iter(), value: None })
+            },
+            _ => Err(self.invalid_type(&visitor)),
+        };
+        self.locate(result)
+    }
+
+    fn deserialize_struct<V: Visitor<'a>>(self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.node {
+            Node::Empty => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.node {
+            Node::Empty => visitor.visit_unit(),
+            _ => {
+                let err = self.invalid_type(&visitor);
+                self.locate(Err(err))
+            },
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'a>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'a>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_f64<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.node {
+            Node::Value { val: Value::Integer(integer), .. } => {
+                let result = visitor.visit_f64(*integer as f64);
+                self.locate(result)
+            },
+            _ => self.visit_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_f64(visitor)
+    }
+
+    /// A Key is read as its own text, even when it names an entry
+    fn deserialize_str<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let string = match self.node {
+            Node::Value { val: Value::Key(key), .. } if !key.starts_with('#') => key,
+            Node::Value { val: Value::String(string), .. } => string,
+            _ => return self.visit_any(visitor),
+        };
+        let result = visitor.visit_borrowed_str(string);
+        self.locate(result)
+    }
+
+    fn deserialize_string<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'a>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        let result = match self.node {
+            // A unit variant, written as a Key or String
+            Node::Value { val: Value::Key(variant), .. } |
+            Node::Value { val: Value::String(variant), .. } if !variant.starts_with('#') => {
+                visitor.visit_enum(variant.as_str().into_deserializer())
+            },
+            // Any other variant, written as a structure with a single item. A
+            // unit variant within a Table is written this way too, its item
+            // an empty entry.
+            Node::Struct(strct) => {
+                let mut items = self.items(strct);
+                if items.len() == 1 {
+                    let (variant, content) = items.remove(0);
+                    visitor.visit_enum(EnumAccess { variant, content })
+                }
+                else {
+                    Err(de::Error::invalid_length(items.len(), &"a single item naming the variant"))
+                }
+            },
+            _ => Err(self.invalid_type(&visitor)),
+        };
+        self.locate(result)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'a>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        <W: Visitor<'a>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char bytes byte_buf
+    }
+}
+
+struct MapAccess<'a> {
+    items: std::vec::IntoIter<(&'a str, Deserializer<'a>)>,
+    value: Option<Deserializer<'a>>,
+}
+
+impl<'a> de::MapAccess<'a> for MapAccess<'a> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'a>>(&mut self, seed: K) -> Result<Option<K::Value>, DeError> {
+        match self.items.next() {
+            Some((key, value)) => {
+                let key = seed.deserialize(de::value::BorrowedStrDeserializer::new(key))
+                              .map_err(|err: DeError| err.at(&value.path, value.span))?;
+                self.value = Some(value);
+                Ok(Some(key))
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'a>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let value = self.value.take().expect("next_value called before next_key");
+        let (path, span) = (value.path.clone(), value.span);
+        seed.deserialize(value).map_err(|err| err.at(&path, span))
+    }
+}
+
+struct SeqAccess<'a> {
+    elems: std::vec::IntoIter<Deserializer<'a>>,
+}
+
+impl<'a> de::SeqAccess<'a> for SeqAccess<'a> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'a>>(&mut self, seed: T) -> Result<Option<T::Value>, DeError> {
+        match self.elems.next() {
+            Some(elem) => {
+                let (path, span) = (elem.path.clone(), elem.span);
+                seed.deserialize(elem).map(Some).map_err(|err| err.at(&path, span))
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elems.len())
+    }
+}
+
+struct EnumAccess<'a> {
+    variant: &'a str,
+    content: Deserializer<'a>,
+}
+
+impl<'a> de::EnumAccess<'a> for EnumAccess<'a> {
+    type Error = DeError;
+    type Variant = Deserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'a>>(self, seed: V) -> Result<(V::Value, Deserializer<'a>), DeError> {
+        let variant = seed.deserialize(de::value::BorrowedStrDeserializer::new(self.variant))?;
+        Ok((variant, self.content))
+    }
+}
+
+impl<'a> de::VariantAccess<'a> for Deserializer<'a> {
+    type Error = DeError;
+
+    fn unit_variant(self) -> Result<(), DeError> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'a>>(self, seed: T) -> Result<T::Value, DeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'a>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'a>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
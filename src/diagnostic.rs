@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use crate::error::{Error, Severity};
+use crate::types::Span;
+
+/* JSON Diagnostics
+ *
+ * Each error is written as one line of JSON, for CI and editors:
+ *
+ *     {"file":"app.jacl","range":{"start":{"line":2,"column":5,"offset":10},
+ *      "end":{...}},"severity":"error","code":"E158","message":"Expected Value",
+ *      "hints":["Make this a value"],"labels":[],"notes":[],"suggestion":null}
+ *
+ * Lines and columns count from 1, and columns count characters. The end
+ * of a range is exclusive. Errors without a location have a null range.
+ */
+
+fn json_str(out: &mut String, string: &str) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn position(out: &mut String, line: usize, column: usize, offset: usize) {
+    let _ = write!(out, "{{\"line\":{},\"column\":{},\"offset\":{}}}", line, column, offset);
+}
+
+/// Write the range of `span`, finding where it ends with `line_at`
+fn range<'a>(out: &mut String, span: Span, line_at: &dyn Fn(usize) -> Option<(usize, &'a str)>) {
+    let mut end_line = span.line;
+    while let Some((start, _)) = line_at(end_line + 1) {
+        if span.end < start {
+            break;
+        }
+        end_line += 1;
+    }
+    let end_col = match line_at(end_line) {
+        Some((start, text)) => {
+            let len = span.end.saturating_sub(start).min(text.len());
+            text.char_indices().take_while(|(idx, _)| *idx < len).count() + 1
+        },
+        None => span.col + span.end.saturating_sub(span.start),
+    };
+
+    out.push_str("{\"start\":");
+    position(out, span.line, span.col, span.start);
+    out.push_str(",\"end\":");
+    position(out, end_line, end_col, span.end);
+    out.push('}');
+}
+
+fn str_list<'s, I: Iterator<Item = &'s str>>(out: &mut String, items: I) {
+    out.push('[');
+    for (idx, item) in items.enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+        json_str(out, item);
+    }
+    out.push(']');
+}
+
+/// Write `err` as a single line of JSON
+pub(crate) fn to_json<'a>(err: &Error, file: Option<&str>, line_at: &dyn Fn(usize) -> Option<(usize, &'a str)>) -> String {
+    let mut out = String::from("{\"file\":");
+    match file {
+        Some(file) => json_str(&mut out, file),
+        None => out.push_str("null"),
+    }
+
+    out.push_str(",\"range\":");
+    match err.span() {
+        Some(span) => range(&mut out, span, line_at),
+        None => out.push_str("null"),
+    }
+
+    out.push_str(",\"severity\":");
+    json_str(&mut out, match err.severity() {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    });
+
+    out.push_str(",\"code\":");
+    match err.code() {
+        Some(code) => json_str(&mut out, &code.to_string()),
+        None => out.push_str("null"),
+    }
+
+    out.push_str(",\"message\":");
+    json_str(&mut out, err.message());
+
+    out.push_str(",\"hints\":");
+    str_list(&mut out, err.hint().into_iter());
+
+    out.push_str(",\"labels\":[");
+    for (idx, label) in err.secondary_labels().iter().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"range\":");
+        range(&mut out, label.span, line_at);
+        out.push_str(",\"message\":");
+        json_str(&mut out, &label.message);
+        out.push('}');
+    }
+    out.push(']');
+
+    out.push_str(",\"notes\":");
+    str_list(&mut out, err.notes().iter().map(String::as_str));
+
+    out.push_str(",\"suggestion\":");
+    match err.suggestion() {
+        Some(suggestion) => {
+            out.push_str("{\"message\":");
+            json_str(&mut out, &suggestion.message);
+            out.push_str(",\"range\":");
+            range(&mut out, suggestion.span, line_at);
+            out.push_str(",\"replacement\":");
+            json_str(&mut out, &suggestion.replacement);
+            out.push('}');
+        },
+        None => out.push_str("null"),
+    }
+    out.push('}');
+    out
+}
@@ -0,0 +1,361 @@
+use std::fmt;
+
+use indexmap::map::IndexMap;
+
+use crate::Jacl;
+use crate::types::{Value, Struct, StructKind, Meta};
+use crate::writer::is_valid_name;
+
+/* Document Model
+ *
+ * A Structure owns its contents, so documents can be built and changed
+ * in code and then written out. It keeps the same rules as the parser:
+ * Tables hold only entries and Maps hold only properties.
+ *
+ * Anonymous entries are stored under keys starting with '#', as in a
+ * parsed document, and are renumbered in order when converted to a Jacl.
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DomError {
+    /// A key or var which could not be written as a name
+    InvalidName(String),
+    /// Maps cannot contain entries
+    EntryInMap(String),
+    /// Tables cannot contain properties
+    PropertyInTable(String),
+    /// There is no entry or property with this name
+    NotFound(String),
+    /// A document must have an Object at its root
+    RootNotObject(StructKind),
+}
+
+impl fmt::Display for DomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomError::InvalidName(name) => write!(f, "'{}' is not a valid name", name),
+            DomError::EntryInMap(key) => write!(f, "Maps cannot contain entries, like '{}'", key),
+            DomError::PropertyInTable(name) => write!(f, "Tables cannot contain properties, like '{}'", name),
+            DomError::NotFound(name) => write!(f, "No entry or property named '{}'", name),
+            DomError::RootNotObject(kind) => write!(f, "A document must have an Object at its root, not a {:?}", kind),
+        }
+    }
+}
+
+impl std::error::Error for DomError {}
+
+fn check_name(name: &str) -> Result<(), DomError> {
+    if is_valid_name(name) { Ok(()) } else { Err(DomError::InvalidName(name.to_string())) }
+}
+
+fn is_anon(key: &str) -> bool {
+    key.starts_with('#')
+}
+
+/// An owned, mutable Object, Table or Map
+#[derive(Clone, Debug)]
+pub struct Structure {
+    kind: StructKind,
+    pub(crate) entries: IndexMap<String, Option<Structure>>,
+    pub(crate) props: IndexMap<String, Value>,
+    next_anon: usize,
+}
+
+impl Structure {
+    pub fn new(kind: StructKind) -> Structure {
+        Structure {
+            kind,
+            entries: IndexMap::new(),
+            props: IndexMap::new(),
+            next_anon: 0,
+        }
+    }
+
+    pub fn object() -> Structure {
+        Structure::new(StructKind::Object)
+    }
+
+    pub fn table() -> Structure {
+        Structure::new(StructKind::Table)
+    }
+
+    pub fn map() -> Structure {
+        Structure::new(StructKind::Map)
+    }
+
+    pub fn builder(kind: StructKind) -> StructureBuilder {
+        StructureBuilder {
+            strct: Structure::new(kind),
+            error: None,
+        }
+    }
+
+    pub fn kind(&self) -> StructKind {
+        self.kind
+    }
+
+    /* Entries */
+
+    /// Every entry in order. Anonymous entries have no key.
+    pub fn entries(&self) -> impl Iterator<Item = (Option<&str>, Option<&Structure>)> {
+        self.entries.iter()
+            .map(|(key, entry)| {
+                let key = if is_anon(key) { None } else { Some(key.as_str()) };
+                (key, entry.as_ref())
+            })
+    }
+
+    /// Is there a named entry with this key? Anonymous entries have no
+    /// key to look them up by.
+    pub fn contains_entry(&self, key: &str) -> bool {
+        !is_anon(key) && self.entries.contains_key(key)
+    }
+
+    /// The structure of a named entry, or None if it is empty or does not
+    /// exist
+    pub fn get_entry(&self, key: &str) -> Option<&Structure> {
+        if is_anon(key) {
+            return None;
+        }
+        self.entries.get(key).and_then(|entry| entry.as_ref())
+    }
+
+    pub fn get_entry_mut(&mut self, key: &str) -> Option<&mut Structure> {
+        if is_anon(key) {
+            return None;
+        }
+        self.entries.get_mut(key).and_then(|entry| entry.as_mut())
+    }
+
+    /// Add an entry, replacing any entry with the same key.
+    /// With no structure the entry is empty, like `atom`.
+    pub fn insert_entry(&mut self, key: &str, entry: Option<Structure>) -> Result<(), DomError> {
+        check_name(key)?;
+        if self.kind == StructKind::Map {
+            return Err(DomError::EntryInMap(key.to_string()));
+        }
+        self.entries.insert(key.to_string(), entry);
+        Ok(())
+    }
+
+    /// Add an anonymous entry
+    pub fn push_entry(&mut self, entry: Structure) -> Result<(), DomError> {
+        if self.kind == StructKind::Map {
+            return Err(DomError::EntryInMap(String::from("anonymous")));
+        }
+        self.push_anon(entry);
+        Ok(())
+    }
+
+    fn push_anon(&mut self, entry: Structure) -> String {
+        let key = format!("#anon{}", self.next_anon);
+        self.next_anon += 1;
+        self.entries.insert(key.clone(), Some(entry));
+        key
+    }
+
+    /// Remove a named entry, returning its structure if it had one.
+    /// Anonymous entries have no key to remove them by, so a structure
+    /// bound to a property is never left without its entry.
+    pub fn remove_entry(&mut self, key: &str) -> Result<Option<Structure>, DomError> {
+        if is_anon(key) {
+            return Err(DomError::NotFound(key.to_string()));
+        }
+        self.entries.shift_remove(key).ok_or_else(|| DomError::NotFound(key.to_string()))
+    }
+
+    /* Properties */
+
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.props.iter().map(|(name, val)| (name.as_str(), val))
+    }
+
+    pub fn contains_property(&self, name: &str) -> bool {
+        self.props.contains_key(name)
+    }
+
+    pub fn get_property(&self, name: &str) -> Option<&Value> {
+        self.props.get(name)
+    }
+
+    pub fn get_property_mut(&mut self, name: &str) -> Option<&mut Value> {
+        self.props.get_mut(name)
+    }
+
+    /// The structure a property is bound to, either directly as in
+    /// `country = {% %}`, or by the key of an entry
+    pub fn resolve_property(&self, name: &str) -> Option<&Structure> {
+        match self.props.get(name) {
+            Some(Value::Key(key)) => self.entries.get(key).and_then(|entry| entry.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn resolve_property_mut(&mut self, name: &str) -> Option<&mut Structure> {
+        match self.props.get(name) {
+            Some(Value::Key(key)) => self.entries.get_mut(key).and_then(|entry| entry.as_mut()),
+            _ => None,
+        }
+    }
+
+    /// Set a property, returning the value it replaces
+    pub fn insert_property<V: Into<Value>>(&mut self, name: &str, val: V) -> Result<Option<Value>, DomError> {
+        check_name(name)?;
+        if self.kind == StructKind::Table {
+            return Err(DomError::PropertyInTable(name.to_string()));
+        }
+        Ok(self.props.insert(name.to_string(), val.into()))
+    }
+
+    /// Bind a property to a new anonymous structure, as in `country = {% %}`
+    pub fn bind_structure(&mut self, name: &str, strct: Structure) -> Result<Option<Value>, DomError> {
+        check_name(name)?;
+        match self.kind {
+            StructKind::Table => Err(DomError::PropertyInTable(name.to_string())),
+            StructKind::Map => Err(DomError::EntryInMap(name.to_string())),
+            StructKind::Object => {
+                let key = self.push_anon(strct);
+                Ok(self.props.insert(name.to_string(), Value::Key(key)))
+            },
+        }
+    }
+
+    pub fn remove_property(&mut self, name: &str) -> Result<Value, DomError> {
+        self.props.shift_remove(name).ok_or_else(|| DomError::NotFound(name.to_string()))
+    }
+
+    /// Change the kind of this structure. Converting to a Table or a Map
+    /// fails if the structure holds anything the new kind cannot.
+    pub fn convert(&mut self, kind: StructKind) -> Result<(), DomError> {
+        match kind {
+            StructKind::Table => {
+                if let Some(name) = self.props.keys().next() {
+                    return Err(DomError::PropertyInTable(name.to_string()));
+                }
+            },
+            StructKind::Map => {
+                if let Some(key) = self.entries.keys().next() {
+                    let key = if is_anon(key) { "anonymous" } else { key };
+                    return Err(DomError::EntryInMap(key.to_string()));
+                }
+            },
+            StructKind::Object => {},
+        }
+        self.kind = kind;
+        Ok(())
+    }
+
+    /* Conversions */
+
+    pub(crate) fn from_struct(strct: &Struct) -> Structure {
+        let (kind, entries, props) = match strct {
+            Struct::Object { entries, props, .. } => (StructKind::Object, Some(entries), Some(props)),
+            Struct::Table { entries, .. } => (StructKind::Table, Some(entries), None),
+            Struct::Map { props, .. } => (StructKind::Map, None, Some(props)),
+        };
+        let mut out = Structure::new(kind);
+        if let Some(entries) = entries {
+            for (key, entry) in entries {
+                out.entries.insert(key.clone(), entry.as_ref().map(Structure::from_struct));
+            }
+            out.next_anon = entries.len();
+        }
+        if let Some(props) = props {
+            out.props = props.clone();
+        }
+        out
+    }
+
+    /// Convert to the parser's representation, numbering anonymous
+    /// entries by their position as the parser would
+    pub(crate) fn to_struct(&self) -> Struct {
+        let mut renamed = IndexMap::new();
+        let mut entries = IndexMap::new();
+        for (idx, (key, entry)) in self.entries.iter().enumerate() {
+            let key = if is_anon(key) {
+                let new = format!("#anon{}", idx);
+                renamed.insert(key.as_str(), new.clone());
+                new
+            }
+            else {
+                key.clone()
+            };
+            entries.insert(key, entry.as_ref().map(Structure::to_struct));
+        }
+        let props = self.props.iter()
+            .map(|(name, val)| {
+                let val = match val {
+                    Value::Key(key) => Value::Key(renamed.get(key.as_str()).cloned().unwrap_or_else(|| key.clone())),
+                    _ => val.clone(),
+                };
+                (name.clone(), val)
+            }).collect::<IndexMap<String, Value>>();
+
+        let meta = Meta::default();
+        match self.kind {
+            StructKind::Object => Struct::Object { entries, props, meta },
+            StructKind::Table => Struct::Table { entries, meta },
+            StructKind::Map => Struct::Map { props, meta },
+        }
+    }
+}
+
+/// Structures are equal if they hold the same kind, entries and properties.
+/// Anonymous entries are compared by position, as their keys depend on what
+/// else the structure has held.
+impl PartialEq for Structure {
+    fn eq(&self, other: &Structure) -> bool {
+        self.to_struct() == other.to_struct()
+    }
+}
+
+impl From<&Jacl> for Structure {
+    fn from(jacl: &Jacl) -> Structure {
+        Structure::from_struct(jacl.data())
+    }
+}
+
+/// Builds a Structure, keeping the first error to report from `build`
+#[derive(Debug)]
+pub struct StructureBuilder {
+    strct: Structure,
+    error: Option<DomError>,
+}
+
+impl StructureBuilder {
+    fn apply<F: FnOnce(&mut Structure) -> Result<(), DomError>>(mut self, f: F) -> StructureBuilder {
+        if self.error.is_none() {
+            if let Err(err) = f(&mut self.strct) {
+                self.error = Some(err);
+            }
+        }
+        self
+    }
+
+    pub fn entry(self, key: &str, entry: Structure) -> StructureBuilder {
+        self.apply(|strct| strct.insert_entry(key, Some(entry)))
+    }
+
+    pub fn empty_entry(self, key: &str) -> StructureBuilder {
+        self.apply(|strct| strct.insert_entry(key, None))
+    }
+
+    pub fn anonymous(self, entry: Structure) -> StructureBuilder {
+        self.apply(|strct| strct.push_entry(entry))
+    }
+
+    pub fn property<V: Into<Value>>(self, name: &str, val: V) -> StructureBuilder {
+        self.apply(|strct| strct.insert_property(name, val).map(|_| ()))
+    }
+
+    pub fn bind(self, name: &str, entry: Structure) -> StructureBuilder {
+        self.apply(|strct| strct.bind_structure(name, entry).map(|_| ()))
+    }
+
+    pub fn build(self) -> Result<Structure, DomError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.strct),
+        }
+    }
+}
@@ -0,0 +1,542 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::cst::{SyntaxTree, SyntaxNode, SyntaxToken, SyntaxKind, SyntaxElement};
+use crate::path::{parse_path, format_path, Segment};
+use crate::types::{Value, StructKind};
+use crate::writer::{write_name, write_value, WriteError};
+
+/* Format-preserving Edits
+ *
+ * Each edit is made as a single change to the text of a SyntaxTree,
+ * touching nothing but the item being changed, so that indentation and
+ * comments elsewhere in the document survive. Where an entry is defined
+ * by several blocks, properties are read from all of them but new items
+ * are added to the first.
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditError {
+    /// The path could not be parsed
+    InvalidPath(String),
+    /// Nothing exists at this path
+    NotFound(String),
+    /// The path leads to an empty entry, or a property, where a structure was needed
+    NotAStruct(String),
+    /// The structure at this path cannot hold the item, like a property in a Table
+    WrongKind(String),
+    AlreadyExists(String),
+    Write(WriteError),
+    /// The edit would have made a valid document invalid
+    Invalid(String),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::InvalidPath(msg) => write!(f, "Invalid path: {}", msg),
+            EditError::NotFound(path) => write!(f, "Nothing found at {}", path),
+            EditError::NotAStruct(path) => write!(f, "{} is not a structure", path),
+            EditError::WrongKind(msg) => write!(f, "{}", msg),
+            EditError::AlreadyExists(path) => write!(f, "{} already exists", path),
+            EditError::Write(err) => write!(f, "{}", err),
+            EditError::Invalid(msg) => write!(f, "Edit would make the document invalid: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl From<WriteError> for EditError {
+    fn from(err: WriteError) -> EditError {
+        EditError::Write(err)
+    }
+}
+
+/* Inspect Items */
+
+fn items(block: &SyntaxNode) -> Vec<&SyntaxNode> {
+    block.child_nodes().into_iter()
+        .filter(|node| matches!(node.kind(), SyntaxKind::Binding | SyntaxKind::Entry))
+        .collect::<Vec<&SyntaxNode>>()
+}
+
+/// The Name tokens naming an item - the vars of a Binding or the keys of an Entry
+fn name_tokens(item: &SyntaxNode) -> Vec<&SyntaxToken> {
+    item.children().iter()
+        .filter_map(|child| match child {
+            SyntaxElement::Token(tok) if tok.kind() == SyntaxKind::Name => Some(tok),
+            _ => None,
+        }).collect::<Vec<&SyntaxToken>>()
+}
+
+fn has_name(tree: &SyntaxTree, item: &SyntaxNode, name: &str) -> bool {
+    name_tokens(item).iter().any(|tok| tok.text(tree) == name)
+}
+
+fn struct_child(item: &SyntaxNode) -> Option<&SyntaxNode> {
+    item.child_nodes().into_iter().find(|node| node.kind().is_struct())
+}
+
+/// The right hand side of a Binding
+fn bound_value(item: &SyntaxNode) -> Option<&SyntaxNode> {
+    item.child_nodes().into_iter().last()
+}
+
+fn block_kind(block: &SyntaxNode) -> StructKind {
+    match block.kind() {
+        SyntaxKind::Table => StructKind::Table,
+        SyntaxKind::Map => StructKind::Map,
+        _ => StructKind::Object,
+    }
+}
+
+/// An empty structure of `kind`, written as the formatter would
+fn empty_text(kind: StructKind) -> &'static str {
+    match kind {
+        StructKind::Object => "{}",
+        StructKind::Table => "[]",
+        StructKind::Map => "{% %}",
+    }
+}
+
+fn split_path(path: &str) -> Result<Vec<Segment>, EditError> {
+    let segments = parse_path(path).map_err(EditError::InvalidPath)?;
+    if segments.is_empty() {
+        return Err(EditError::InvalidPath(String::from("Path is empty")));
+    }
+    Ok(segments)
+}
+
+fn last_key(segments: &[Segment]) -> Result<&str, EditError> {
+    match segments.last() {
+        Some(Segment::Key(key)) => Ok(key),
+        _ => Err(EditError::InvalidPath(format!("{} does not end with a name", format_path(segments)))),
+    }
+}
+
+impl SyntaxTree {
+    /// Every block which defines the structure at `segments`, in source order
+    fn blocks(&self, segments: &[Segment]) -> Result<Vec<&SyntaxNode>, EditError> {
+        let mut blocks = vec![self.root()];
+        for (i, seg) in segments.iter().enumerate() {
+            let here = format_path(&segments[..=i]);
+            let mut found = false;
+            let mut next = Vec::new();
+            match seg {
+                Segment::Key(key) => {
+                    for block in &blocks {
+                        for item in items(block) {
+                            if has_name(self, item, key) {
+                                found = true;
+                                if let Some(strct) = struct_child(item) {
+                                    next.push(strct);
+                                }
+                            }
+                        }
+                    }
+                },
+                Segment::Index(idx) => {
+                    if let Some(strcts) = self.entries(&blocks).into_iter().nth(*idx) {
+                        found = true;
+                        next = strcts;
+                    }
+                },
+            }
+            if !found {
+                return Err(EditError::NotFound(here));
+            }
+            if next.is_empty() {
+                return Err(EditError::NotAStruct(here));
+            }
+            blocks = next;
+        }
+        Ok(blocks)
+    }
+
+    /// The blocks defining each entry of `blocks`, in the order the parser
+    /// numbers them. Like the parser, this counts the anonymous entry made
+    /// by binding a property to a structure, and counts an entry defined
+    /// by several blocks once.
+    fn entries<'t>(&self, blocks: &[&'t SyntaxNode]) -> Vec<Vec<&'t SyntaxNode>> {
+        let mut keys: Vec<Option<&str>> = Vec::new();
+        let mut entries: Vec<Vec<&'t SyntaxNode>> = Vec::new();
+        for item in blocks.iter().flat_map(|block| items(block)) {
+            let strct = struct_child(item);
+            if item.kind() == SyntaxKind::Binding {
+                if let Some(strct) = strct {
+                    keys.push(None);
+                    entries.push(vec![strct]);
+                }
+                continue;
+            }
+            let names = name_tokens(item);
+            if names.is_empty() {
+                keys.push(None);
+                entries.push(strct.into_iter().collect());
+            }
+            for tok in names {
+                let key = tok.text(self);
+                let idx = match keys.iter().position(|k| *k == Some(key)) {
+                    Some(idx) => idx,
+                    None => {
+                        keys.push(Some(key));
+                        entries.push(Vec::new());
+                        entries.len() - 1
+                    },
+                };
+                entries[idx].extend(strct);
+            }
+        }
+        entries
+    }
+
+    /// The last Binding of `name` within `blocks`, which holds its current value
+    fn last_binding<'t>(&self, blocks: &[&'t SyntaxNode], name: &str) -> Option<&'t SyntaxNode> {
+        blocks.iter()
+              .flat_map(|block| items(block))
+              .rfind(|item| item.kind() == SyntaxKind::Binding && has_name(self, item, name))
+    }
+
+    fn leaf_at(&self, offset: usize) -> Option<&SyntaxToken> {
+        self.root().tokens().into_iter().find(|leaf| leaf.range().start >= offset)
+    }
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.source()[..offset].rfind('\n').map(|idx| idx + 1).unwrap_or(0)
+    }
+
+    /// The whitespace at the start of the line containing `offset`
+    fn indent_at(&self, offset: usize) -> &str {
+        let start = self.line_start(offset);
+        let line = &self.source()[start..];
+        let end = line.find(|c: char| !(c == ' ' || c == '\t')).unwrap_or(line.len());
+        &line[..end]
+    }
+
+    /// One level of indentation below `indent`. This is a tab where the
+    /// file already indents with tabs, otherwise the smallest indent found
+    /// in the file, and 4 spaces in a file with no indented lines.
+    fn indent_unit(&self, indent: &str) -> String {
+        if indent.contains('\t') {
+            return String::from("\t");
+        }
+        let indents = self.source().lines()
+                          .filter(|line| !line.trim().is_empty())
+                          .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+                          .filter(|indent| !indent.is_empty())
+                          .collect::<Vec<&str>>();
+        if indents.iter().any(|indent| indent.starts_with('\t')) {
+            return String::from("\t");
+        }
+        indents.into_iter().min_by_key(|indent| indent.len()).unwrap_or("    ").to_string()
+    }
+
+    /// The end of `item` together with any comment following it on the same line
+    fn item_end(&self, item: &SyntaxNode) -> usize {
+        let mut end = item.range().end;
+        for leaf in self.root().tokens() {
+            if leaf.range().start < end {
+                continue;
+            }
+            if leaf.kind().is_trivia() {
+                end = leaf.range().end;
+            }
+            else {
+                break;
+            }
+        }
+        end
+    }
+
+    /// Work out how to add `text` as a new item of `block`, after `anchor` if given
+    fn insertion(&self, block: &SyntaxNode, anchor: Option<&SyntaxNode>, text: &str) -> (Range<usize>, String) {
+        let anchor = anchor.or_else(|| items(block).into_iter().last());
+        if let Some(item) = anchor {
+            let end = self.item_end(item);
+            let indent = self.indent_at(item.range().start);
+            match self.leaf_at(end) {
+                Some(leaf) if leaf.kind() == SyntaxKind::Break && leaf.text(self) == "\n" => {
+                    (end..end, format!("\n{}{}", indent, text))
+                },
+                None => {
+                    (end..end, format!("\n{}{}", indent, text))
+                },
+                _ => {
+                    let end = item.range().end;
+                    (end..end, format!("; {}", text))
+                }
+            }
+        }
+        else if block.kind() == SyntaxKind::Root {
+            let end = self.source().len();
+            if end == 0 || self.source().ends_with('\n') {
+                (end..end, format!("{}\n", text))
+            }
+            else {
+                (end..end, format!("\n{}\n", text))
+            }
+        }
+        else {
+            let toks = block.tokens();
+            let open = toks.first().expect("Structure without opening bracket");
+            let close = toks.last().filter(|tok| tok.kind() != open.kind() && !tok.kind().is_trivia());
+            let outer = self.indent_at(block.range().start);
+            let inner = format!("{}{}", outer, self.indent_unit(outer));
+            let multiline = toks.iter().any(|tok| tok.kind() == SyntaxKind::Break);
+            match close {
+                Some(close) if !multiline => {
+                    (open.range().end..close.range().start, format!("\n{}{}\n{}", inner, text, outer))
+                },
+                _ => {
+                    let end = open.range().end;
+                    (end..end, format!("\n{}{}", inner, text))
+                }
+            }
+        }
+    }
+
+    /// The comment following `item` on the same line, if any
+    fn trailing_comment(&self, item: &SyntaxNode) -> Option<&SyntaxToken> {
+        self.root().tokens().into_iter()
+            .filter(|leaf| leaf.range().start >= item.range().end)
+            .find(|leaf| leaf.kind() != SyntaxKind::Whitespace)
+            .filter(|leaf| leaf.kind() == SyntaxKind::Comment)
+    }
+
+    /// Work out how to delete `item`, taking its whole line with it if it
+    /// stands alone. A comment after it is kept where it was.
+    fn removal(&self, item: &SyntaxNode) -> Range<usize> {
+        let start = item.range().start;
+        if let Some(comment) = self.trailing_comment(item) {
+            return start..comment.range().start;
+        }
+        let end = self.item_end(item);
+        let line_start = self.line_start(start);
+        let alone = self.source()[line_start..start].trim().is_empty();
+        match self.leaf_at(end) {
+            Some(leaf) if leaf.kind() == SyntaxKind::Break && leaf.text(self) == "\n" && alone => {
+                line_start..leaf.range().end
+            },
+            None if alone => {
+                // The last line - take the newline before it instead
+                line_start.saturating_sub(1)..end
+            },
+            Some(leaf) if leaf.kind() == SyntaxKind::Break && leaf.text(self) == ";" => {
+                let mut end = leaf.range().end;
+                let rest = &self.source()[end..];
+                end += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+                start..end
+            },
+            _ => start..end,
+        }
+    }
+
+    /// Work out how to remove one of several names from an item,
+    /// along with the separator next to it
+    fn name_removal(&self, item: &SyntaxNode, name: &str) -> Option<Range<usize>> {
+        let names = name_tokens(item);
+        let pos = names.iter().position(|tok| tok.text(self) == name)?;
+        if names.len() < 2 {
+            return None;
+        }
+        if pos + 1 < names.len() {
+            Some(names[pos].range().start..names[pos + 1].range().start)
+        }
+        else {
+            Some(names[pos - 1].range().end..names[pos].range().end)
+        }
+    }
+
+    /// Apply a change to the text, undoing it if it breaks a valid document
+    fn apply(&mut self, range: Range<usize>, text: &str) -> Result<(), EditError> {
+        let valid = self.lower().is_ok();
+        let before = self.clone();
+        self.splice(range, text);
+        if valid {
+            if let Err(err) = self.lower() {
+                let msg = err.errors()[0].message().to_string();
+                *self = before;
+                return Err(EditError::Invalid(msg));
+            }
+        }
+        Ok(())
+    }
+
+    /// Make an edit of several changes, undoing them all if any fails
+    fn all_or_nothing<F>(&mut self, edit: F) -> Result<(), EditError>
+    where
+        F: FnOnce(&mut SyntaxTree) -> Result<(), EditError>,
+    {
+        let before = self.clone();
+        let result = edit(self);
+        if result.is_err() {
+            *self = before;
+        }
+        result
+    }
+
+    /// Set the property at `path` to `value`, adding it if it does not exist.
+    ///
+    /// If the path ends with an index into a Tuple property, like
+    /// `alarm.freq[1]`, just that element is replaced.
+    pub fn set_property(&mut self, path: &str, value: &Value) -> Result<(), EditError> {
+        let segments = split_path(path)?;
+        let text = write_value(value)?;
+
+        if let [parent @ .., Segment::Key(name), Segment::Index(idx)] = segments.as_slice() {
+            let range = {
+                let blocks = self.blocks(parent)?;
+                let binding = self.last_binding(&blocks, name)
+                                  .ok_or_else(|| EditError::NotFound(format_path(&segments[..segments.len() - 1])))?;
+                let tuple = bound_value(binding)
+                                .filter(|node| node.kind() == SyntaxKind::Tuple)
+                                .ok_or_else(|| EditError::WrongKind(format!("{} is not a Tuple", name)))?;
+                let elem = tuple.child_nodes().into_iter().nth(*idx)
+                                .ok_or_else(|| EditError::NotFound(format_path(&segments)))?;
+                elem.range()
+            };
+            return self.apply(range, &text);
+        }
+
+        let name = last_key(&segments)?;
+        let parent = &segments[..segments.len() - 1];
+        let existing = {
+            let blocks = self.blocks(parent)?;
+            self.last_binding(&blocks, name).map(|binding| {
+                (self.name_removal(binding, name), bound_value(binding).map(|node| node.range()))
+            })
+        };
+        match existing {
+            // Bound alone - replace the value in place
+            Some((None, Some(range))) => self.apply(range, &text),
+            // Bound alongside other vars - unbind it there and bind it afresh
+            Some((Some(range), _)) => self.all_or_nothing(|tree| {
+                tree.apply(range, "")?;
+                tree.insert_property(path, value)
+            }),
+            _ => self.insert_property(path, value),
+        }
+    }
+
+    /// Add a new property at `path`, which must not already exist
+    pub fn insert_property(&mut self, path: &str, value: &Value) -> Result<(), EditError> {
+        let segments = split_path(path)?;
+        let name = last_key(&segments)?;
+        let text = format!("{} = {}", write_name(name)?, write_value(value)?);
+        let (range, text) = {
+            let blocks = self.blocks(&segments[..segments.len() - 1])?;
+            if self.last_binding(&blocks, name).is_some() {
+                return Err(EditError::AlreadyExists(format_path(&segments)));
+            }
+            let block = blocks[0];
+            if block_kind(block) == StructKind::Table {
+                return Err(EditError::WrongKind(String::from("Tables cannot contain properties")));
+            }
+            let anchor = items(block).into_iter()
+                                     .rfind(|item| item.kind() == SyntaxKind::Binding);
+            self.insertion(block, anchor, &text)
+        };
+        self.apply(range, &text)
+    }
+
+    /// Remove every binding of the property at `path`
+    pub fn remove_property(&mut self, path: &str) -> Result<(), EditError> {
+        let segments = split_path(path)?;
+        let name = last_key(&segments)?;
+        let parent = &segments[..segments.len() - 1];
+        self.all_or_nothing(|tree| {
+            let mut removed = false;
+            loop {
+                let range = {
+                    let blocks = tree.blocks(parent)?;
+                    match tree.last_binding(&blocks, name) {
+                        Some(binding) => {
+                            tree.name_removal(binding, name)
+                                .unwrap_or_else(|| tree.removal(binding))
+                        },
+                        None => break,
+                    }
+                };
+                tree.apply(range, "")?;
+                removed = true;
+            }
+            if removed { Ok(()) } else { Err(EditError::NotFound(format_path(&segments))) }
+        })
+    }
+
+    /// Add a new entry at `path`, which must not already exist.
+    /// With no `kind` the entry is empty, like `atom`.
+    pub fn insert_entry(&mut self, path: &str, kind: Option<StructKind>) -> Result<(), EditError> {
+        let segments = split_path(path)?;
+        let key = last_key(&segments)?;
+        let text = match kind {
+            Some(kind) => format!("{} {}", write_name(key)?, empty_text(kind)),
+            None => write_name(key)?,
+        };
+        let (range, text) = {
+            let blocks = self.blocks(&segments[..segments.len() - 1])?;
+            let exists = blocks.iter()
+                               .flat_map(|block| items(block))
+                               .any(|item| item.kind() == SyntaxKind::Entry && has_name(self, item, key));
+            if exists {
+                return Err(EditError::AlreadyExists(format_path(&segments)));
+            }
+            let block = blocks[0];
+            if block_kind(block) == StructKind::Map {
+                return Err(EditError::WrongKind(String::from("Maps cannot contain entries")));
+            }
+            self.insertion(block, None, &text)
+        };
+        self.apply(range, &text)
+    }
+
+    /// Remove the entry at `path`, along with every block defining it.
+    /// Compound definitions like `a + b { }` lose just the removed key.
+    pub fn remove_entry(&mut self, path: &str) -> Result<(), EditError> {
+        let segments = split_path(path)?;
+        let key = last_key(&segments)?;
+        let parent = &segments[..segments.len() - 1];
+        self.all_or_nothing(|tree| {
+            let mut removed = false;
+            loop {
+                let range = {
+                    let blocks = tree.blocks(parent)?;
+                    let entry = blocks.iter()
+                                      .flat_map(|block| items(block))
+                                      .find(|item| item.kind() == SyntaxKind::Entry && has_name(tree, item, key));
+                    match entry {
+                        Some(entry) => {
+                            tree.name_removal(entry, key)
+                                .unwrap_or_else(|| tree.removal(entry))
+                        },
+                        None => break,
+                    }
+                };
+                tree.apply(range, "")?;
+                removed = true;
+            }
+            if removed { Ok(()) } else { Err(EditError::NotFound(format_path(&segments))) }
+        })
+    }
+
+    /// Append an anonymous structure of `kind` to the Table or Object at `path`
+    pub fn push_item(&mut self, path: &str, kind: StructKind) -> Result<(), EditError> {
+        self.append_item(path, empty_text(kind))
+    }
+
+    /// Append `item`, written as JACL, to the structure at `path`.
+    /// An empty path refers to the root Object.
+    pub fn append_item(&mut self, path: &str, item: &str) -> Result<(), EditError> {
+        let segments = parse_path(path).map_err(EditError::InvalidPath)?;
+        let check = SyntaxTree::parse(item);
+        if check.lower().is_err() || items(check.root()).len() != 1 {
+            return Err(EditError::Invalid(format!("'{}' is not a single item", item)));
+        }
+        let (range, text) = {
+            let blocks = self.blocks(&segments)?;
+            self.insertion(blocks[0], None, item.trim())
+        };
+        self.apply(range, &text)
+    }
+}
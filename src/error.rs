@@ -1,48 +1,327 @@
-use std::result;
+use std::fmt;
+
 use crate::Lines;
 use crate::tokeniser::Token;
-use crate::util::token_line;
+use crate::types::Span;
+use crate::util::line_text;
+use crate::render::{render, RenderConfig};
+use crate::diagnostic::to_json;
+
+/// Every error JACL itself can report. The number of each is shown as
+/// `E<n>` when it is rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// E1: A bug in JACL rather than a problem with the input
+    Internal,
+    /// E100: An Integer too large for 64 bits
+    IntegerTooLarge,
+    /// E101: A Float too large for 64 bits
+    FloatTooLarge,
+    /// E102: A character which cannot start any token
+    UnexpectedCharacter,
+    /// E103: `%` not followed by `}`
+    PercentWithoutBrace,
+    /// E104: `%` followed by a newline
+    PercentBeforeNewline,
+    /// E105: A newline within a String or other token
+    UnexpectedNewline,
+    /// E106: A String with no closing quote
+    UnterminatedString,
+    /// E150: A different token was expected
+    ExpectedToken,
+    /// E151: A token was expected, but the input ended
+    ExpectedTokenAtEnd,
+    /// E152: A closing bracket with no opening bracket
+    UnmatchedBracket,
+    /// E153: An Object, Table or Map was expected
+    ExpectedStruct,
+    /// E154: The input ended partway through an item
+    UnexpectedEnd,
+    /// E155: The input ended while looking ahead
+    UnexpectedEndLookahead,
+    /// E156: An entry within a Map
+    EntryInMap,
+    /// E157: A binding within a Table
+    BindingInTable,
+    /// E158: A value was expected
+    ExpectedValue,
+    /// E159: An entry redefined as something other than the Map it is
+    AlreadyMap,
+    /// E160: An entry redefined as something other than the Table it is
+    AlreadyTable,
+    /// E161: An entry redefined as something other than the Object it is
+    AlreadyObject,
+    /// E162: An entry redefined without anything to add
+    EmptyRedefinition,
+    /// E163: A binding without `=` or `,` after its names
+    ExpectedEqualsOrComma,
+    /// E164: A Tuple which is not a list of values separated by `,`
+    InvalidTuple,
+    /// E165: A reference to a var which has not been bound
+    NoSuchVar,
+    /// E200: A schema which is not made of valid rules
+    InvalidSchema,
+    /// E201: An item of a type its schema does not allow
+    WrongType,
+    /// E202: A required entry or property which is missing
+    MissingItem,
+    /// E203: An entry or property its schema does not list
+    UnexpectedItem,
+    /// E204: A number outside the bounds of its schema
+    OutOfRange,
+    /// E205: A String or Tuple of the wrong length
+    WrongLength,
+    /// E206: A value which is not one of those its schema allows
+    NotOneOf,
+    /// E207: A String which does not match the pattern of its schema
+    NoPatternMatch,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 32] = [
+        ErrorCode::Internal,
+        ErrorCode::IntegerTooLarge,
+        ErrorCode::FloatTooLarge,
+        ErrorCode::UnexpectedCharacter,
+        ErrorCode::PercentWithoutBrace,
+        ErrorCode::PercentBeforeNewline,
+        ErrorCode::UnexpectedNewline,
+        ErrorCode::UnterminatedString,
+        ErrorCode::ExpectedToken,
+        ErrorCode::ExpectedTokenAtEnd,
+        ErrorCode::UnmatchedBracket,
+        ErrorCode::ExpectedStruct,
+        ErrorCode::UnexpectedEnd,
+        ErrorCode::UnexpectedEndLookahead,
+        ErrorCode::EntryInMap,
+        ErrorCode::BindingInTable,
+        ErrorCode::ExpectedValue,
+        ErrorCode::AlreadyMap,
+        ErrorCode::AlreadyTable,
+        ErrorCode::AlreadyObject,
+        ErrorCode::EmptyRedefinition,
+        ErrorCode::ExpectedEqualsOrComma,
+        ErrorCode::InvalidTuple,
+        ErrorCode::NoSuchVar,
+        ErrorCode::InvalidSchema,
+        ErrorCode::WrongType,
+        ErrorCode::MissingItem,
+        ErrorCode::UnexpectedItem,
+        ErrorCode::OutOfRange,
+        ErrorCode::WrongLength,
+        ErrorCode::NotOneOf,
+        ErrorCode::NoPatternMatch,
+    ];
+
+    /// The number shown after the `E`
+    pub fn number(self) -> u16 {
+        match self {
+            ErrorCode::Internal => 1,
+            ErrorCode::IntegerTooLarge => 100,
+            ErrorCode::FloatTooLarge => 101,
+            ErrorCode::UnexpectedCharacter => 102,
+            ErrorCode::PercentWithoutBrace => 103,
+            ErrorCode::PercentBeforeNewline => 104,
+            ErrorCode::UnexpectedNewline => 105,
+            ErrorCode::UnterminatedString => 106,
+            ErrorCode::ExpectedToken => 150,
+            ErrorCode::ExpectedTokenAtEnd => 151,
+            ErrorCode::UnmatchedBracket => 152,
+            ErrorCode::ExpectedStruct => 153,
+            ErrorCode::UnexpectedEnd => 154,
+            ErrorCode::UnexpectedEndLookahead => 155,
+            ErrorCode::EntryInMap => 156,
+            ErrorCode::BindingInTable => 157,
+            ErrorCode::ExpectedValue => 158,
+            ErrorCode::AlreadyMap => 159,
+            ErrorCode::AlreadyTable => 160,
+            ErrorCode::AlreadyObject => 161,
+            ErrorCode::EmptyRedefinition => 162,
+            ErrorCode::ExpectedEqualsOrComma => 163,
+            ErrorCode::InvalidTuple => 164,
+            ErrorCode::NoSuchVar => 165,
+            ErrorCode::InvalidSchema => 200,
+            ErrorCode::WrongType => 201,
+            ErrorCode::MissingItem => 202,
+            ErrorCode::UnexpectedItem => 203,
+            ErrorCode::OutOfRange => 204,
+            ErrorCode::WrongLength => 205,
+            ErrorCode::NotOneOf => 206,
+            ErrorCode::NoPatternMatch => 207,
+        }
+    }
+
+    pub fn from_number(number: u16) -> Option<ErrorCode> {
+        ErrorCode::ALL.iter().copied().find(|code| code.number() == number)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{}", self.number())
+    }
+}
+
+/// How serious a diagnostic is. Everything JACL reports is an Error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A message attached to a span of the input
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
 
-#[derive(Clone, Debug)]
-pub struct Error<'src> {
-    code: u8,
+/// A change to the input which would fix the problem
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub message: String,
+    /// The text to replace. An empty span inserts the replacement.
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// The parts of an Error which most do not have. They are boxed to keep
+/// Error small, since the parser returns it in every Result.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Details {
+    secondary: Vec<Label>,
+    notes: Vec<String>,
+    suggestion: Option<Suggestion>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    code: Option<ErrorCode>,
+    severity: Severity,
     msg: String,
-    token: Option<Token<'src>>,
+    span: Option<Span>,
     hint: Option<String>,
+    details: Option<Box<Details>>,
 }
 
-impl<'src> Error<'src> {
-    pub fn basic(code: u8, msg: String) -> Error<'src> {
+impl Error {
+    fn new(code: Option<ErrorCode>, msg: String, span: Option<Span>, hint: Option<String>) -> Error {
         Error {
             code,
+            severity: Severity::Error,
             msg,
-            token: None,
-            hint: None,
+            span,
+            hint,
+            details: None,
         }
     }
 
-    pub fn detailed(code: u8, msg: String,
-                token: Token<'src>, hint: String) -> Error<'src> {
-        Error {
-            code,
-            msg,
-            token: Some(token),
-            hint: Some(hint),
-        }
+    fn details_mut(&mut self) -> &mut Details {
+        self.details.get_or_insert_with(Default::default)
     }
 
-    pub fn render(&self, input: &'src str, lines: &Lines) -> String {
-        if let (Some(ref token), Some(ref hint)) = (&self.token, &self.hint) {
-            let code_line = format!("{:<3}| {}", token.lno, token_line(input, lines, token));
-            let ptr_line = format!("{}{}", " ".repeat(4 + token.col), "^".repeat(token.len));
-            let hint_line = format!("Hint: {}", hint);
-            format!("[E{}] {}\n{}\n{}\n{}\n", self.code, self.msg, code_line, ptr_line, hint_line)
-        }
-        else {
-            format!("[E{}] {}\n", self.code, self.msg)
-        }
+    pub fn basic(code: ErrorCode, msg: String) -> Error {
+        Error::new(Some(code), msg, None, None)
+    }
+
+    pub fn detailed(code: ErrorCode, msg: String,
+                token: Token<'_>, hint: String) -> Error {
+        Error::spanned(code, msg, token.span(), hint)
     }
-}
 
-pub type Result<'src, T> = result::Result<T, Error<'src>>;
+    pub fn spanned(code: ErrorCode, msg: String,
+                   span: Span, hint: String) -> Error {
+        Error::new(Some(code), msg, Some(span), Some(hint))
+    }
+
+    /// An error raised by the application rather than by JACL itself.
+    /// These are rendered without an error code.
+    pub fn custom(msg: String, span: Span, hint: Option<String>) -> Error {
+        Error::new(None, msg, Some(span), hint)
+    }
 
+    pub fn with_severity(mut self, severity: Severity) -> Error {
+        self.severity = severity;
+        self
+    }
+
+    /// Point at another span which helps explain the problem
+    pub fn with_label<M: Into<String>>(mut self, span: Span, message: M) -> Error {
+        self.details_mut().secondary.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note<M: Into<String>>(mut self, note: M) -> Error {
+        self.details_mut().notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion<M: Into<String>, R: Into<String>>(mut self, message: M, span: Span, replacement: R) -> Error {
+        self.details_mut().suggestion = Some(Suggestion { message: message.into(), span, replacement: replacement.into() });
+        self
+    }
+
+    /// The code of the error, or None if it was raised by the application
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.code
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// The span the error is about, labelled with its hint
+    pub fn primary_label(&self) -> Option<Label> {
+        self.span.map(|span| Label { span, message: self.hint.clone().unwrap_or_default() })
+    }
+
+    /// Other spans which help explain the error
+    pub fn secondary_labels(&self) -> &[Label] {
+        self.details.as_ref().map_or(&[], |details| &details.secondary)
+    }
+
+    pub fn notes(&self) -> &[String] {
+        self.details.as_ref().map_or(&[], |details| &details.notes)
+    }
+
+    pub fn suggestion(&self) -> Option<&Suggestion> {
+        self.details.as_ref().and_then(|details| details.suggestion.as_ref())
+    }
+
+    /// Line and column at which the error was found, if known
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.span.map(|span| (span.line, span.col))
+    }
+
+    pub fn render(&self, input: &str, lines: &Lines) -> String {
+        self.render_with(input, lines, &RenderConfig::default())
+    }
+
+    pub fn render_with(&self, input: &str, lines: &Lines, config: &RenderConfig) -> String {
+        let line_at = |lno: usize| {
+            line_text(input, lines, lno).map(|text| (lines[lno - 1].0, text))
+        };
+        render(self, &line_at, config)
+    }
+
+    /// This error as a line of JSON, with `file` as the name of the input
+    pub fn to_json(&self, input: &str, lines: &Lines, file: Option<&str>) -> String {
+        let line_at = |lno: usize| {
+            line_text(input, lines, lno).map(|text| (lines[lno - 1].0, text))
+        };
+        to_json(self, file, &line_at)
+    }
+}
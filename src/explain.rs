@@ -0,0 +1,533 @@
+use std::str::FromStr;
+
+use crate::error::ErrorCode;
+
+/* Error Catalog
+ *
+ * A long explanation of every error code, with examples, in the manner
+ * of `rustc --explain`. Examples are indented by four spaces.
+ */
+
+impl ErrorCode {
+    /// A long explanation of this error, with examples of how it is caused
+    /// and how to fix it
+    pub fn explanation(self) -> &'static str {
+        match self {
+            ErrorCode::Internal => E1,
+            ErrorCode::IntegerTooLarge => E100,
+            ErrorCode::FloatTooLarge => E101,
+            ErrorCode::UnexpectedCharacter => E102,
+            ErrorCode::PercentWithoutBrace => E103,
+            ErrorCode::PercentBeforeNewline => E104,
+            ErrorCode::UnexpectedNewline => E105,
+            ErrorCode::UnterminatedString => E106,
+            ErrorCode::ExpectedToken => E150,
+            ErrorCode::ExpectedTokenAtEnd => E151,
+            ErrorCode::UnmatchedBracket => E152,
+            ErrorCode::ExpectedStruct => E153,
+            ErrorCode::UnexpectedEnd => E154,
+            ErrorCode::UnexpectedEndLookahead => E155,
+            ErrorCode::EntryInMap => E156,
+            ErrorCode::BindingInTable => E157,
+            ErrorCode::ExpectedValue => E158,
+            ErrorCode::AlreadyMap => E159,
+            ErrorCode::AlreadyTable => E160,
+            ErrorCode::AlreadyObject => E161,
+            ErrorCode::EmptyRedefinition => E162,
+            ErrorCode::ExpectedEqualsOrComma => E163,
+            ErrorCode::InvalidTuple => E164,
+            ErrorCode::NoSuchVar => E165,
+            ErrorCode::InvalidSchema => E200,
+            ErrorCode::WrongType => E201,
+            ErrorCode::MissingItem => E202,
+            ErrorCode::UnexpectedItem => E203,
+            ErrorCode::OutOfRange => E204,
+            ErrorCode::WrongLength => E205,
+            ErrorCode::NotOneOf => E206,
+            ErrorCode::NoPatternMatch => E207,
+        }
+    }
+}
+
+/// Codes may be written as `E162`, `e162` or `162`
+impl FromStr for ErrorCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<ErrorCode, String> {
+        let digits = code.trim().trim_start_matches(['E', 'e']);
+        digits.parse::<u16>().ok()
+            .and_then(ErrorCode::from_number)
+            .ok_or_else(|| format!("'{}' is not a JACL error code", code))
+    }
+}
+
+/// The long explanation of an error code such as `E162`, or None if
+/// there is no such code
+pub fn explain(code: &str) -> Option<&'static str> {
+    code.parse::<ErrorCode>().ok().map(ErrorCode::explanation)
+}
+
+const E1: &str = "\
+E1: Internal error
+
+The parser reached a state which should not be possible. This is a bug in
+JACL rather than a problem with your document.
+
+Please report it along with the document which caused it.";
+
+const E100: &str = "\
+E100: Integer too large
+
+Integers are stored in 64 bits, so they cannot be larger than
+9223372036854775807.
+
+    port = 99999999999999999999
+
+If the number is an identifier rather than a quantity, store it as a
+String instead:
+
+    serial = \"99999999999999999999\"";
+
+const E101: &str = "\
+E101: Float too large
+
+A number with a decimal point could not be read as a 64-bit Float.
+
+Floats too large to represent are read as infinity rather than raising
+this error, so it is rarely seen. Infinite Floats cannot be written back
+out, so keep Floats within a reasonable range.";
+
+const E102: &str = "\
+E102: Unexpected character
+
+A character was found which cannot begin any part of a JACL document.
+
+    name = 'martin'
+
+Strings must be surrounded by double quotes:
+
+    name = \"martin\"";
+
+const E103: &str = "\
+E103: '%' was not followed by '}'
+
+A Map is closed with '%}', with nothing between the two characters.
+
+    limits {% depth = 3 % }
+
+Remove the space:
+
+    limits {% depth = 3 %}";
+
+const E104: &str = "\
+E104: '%' was followed by a newline
+
+A Map is closed with '%}', but here the '%' is the last character on its
+line.
+
+    limits {% depth = 3 %
+    }
+
+Put the '}' straight after the '%':
+
+    limits {% depth = 3 %}";
+
+const E105: &str = "\
+E105: Unexpected newline
+
+A line ended partway through a String. Strings cannot span lines.
+
+    motd = \"Welcome
+    to the server\"
+
+Use '\\n' for a line break within a String:
+
+    motd = \"Welcome\\nto the server\"";
+
+const E106: &str = "\
+E106: Unterminated String
+
+The document ended before the closing quote of a String.
+
+    name = \"martin
+
+Close the String with '\"':
+
+    name = \"martin\"";
+
+const E150: &str = "\
+E150: Expected a different token
+
+The parser needed a particular kind of token, such as a name or a bracket,
+and found something else. The hint says what was found instead.
+
+    mark + = 1
+
+A '+' joins the keys of entries, so another key must follow it:
+
+    mark + jeff {
+        job = \"Teacher\"
+    }";
+
+const E151: &str = "\
+E151: Expected a token but found the end of the document
+
+The document ended while the parser still needed something, most often
+the closing bracket of a structure.
+
+    servers {
+        freenode { }
+
+Close every structure which is opened:
+
+    servers {
+        freenode { }
+    }";
+
+const E152: &str = "\
+E152: Unmatched closing bracket
+
+A closing bracket was found with no opening bracket to match it.
+
+    servers {
+        freenode { }
+    }
+    }
+
+Remove the extra bracket, or add the opening bracket it was meant to
+close.";
+
+const E153: &str = "\
+E153: Expected a structure
+
+After the keys of an entry, the parser expected an Object, Table or Map,
+or the end of the line for an empty entry.
+
+    port 6667
+
+Properties are bound with '=':
+
+    port = 6667
+
+Entries are followed by their structure:
+
+    freenode {
+        port = 6667
+    }";
+
+const E154: &str = "\
+E154: Unexpected end of the document
+
+The document ended partway through an item, such as within a Tuple.
+
+    primes = (2, 3, 5
+
+Finish the item:
+
+    primes = (2, 3, 5)";
+
+const E155: &str = "\
+E155: Unexpected end of the document
+
+The document ended while the parser was looking ahead to decide what kind
+of item a name begins. The end of every document counts as a line break,
+so this error is rarely seen.
+
+Finish the item which was being written.";
+
+const E156: &str = "\
+E156: Maps cannot contain entries
+
+A Map, written with '{%' and '%}', holds only properties.
+
+    limits {%
+        depth = 3
+        strict
+    %}
+
+Bind the value to a var, or use an Object, which can hold entries and
+properties together:
+
+    limits {
+        depth = 3
+        strict
+    }";
+
+const E157: &str = "\
+E157: Tables cannot contain bindings
+
+A Table, written with '[' and ']', holds only entries.
+
+    filters [
+        action = \"ignore\"
+    ]
+
+Put the properties in an anonymous Object within the Table:
+
+    filters [
+        { action = \"ignore\" }
+    ]";
+
+const E158: &str = "\
+E158: Expected a value
+
+A var was bound with '=', but no value followed it.
+
+    name =
+
+Give the var a value, such as a String, Integer, Float, Boolean, Tuple,
+Key or Var:
+
+    name = \"martin\"";
+
+const E159: &str = "\
+E159: Entry already defined as a Map
+
+This entry was first defined as a Map. Objects and Tables may be
+redefined, with each definition merged into the first, but a Map cannot
+be redefined at all.
+
+    limits {% depth = 3 %}
+    limits { strict }
+
+Give every property of a Map in its one definition:
+
+    limits {% depth = 3; strict = true %}";
+
+const E160: &str = "\
+E160: Entry already defined as a Table
+
+Definitions of the same entry are merged, so they must all be of the same
+kind. This entry was first defined as a Table.
+
+    ingredients [ cherries ]
+    ingredients { sugar = 100 }
+
+Redefine it as a Table, whose entries are appended to the first
+definition:
+
+    ingredients [ cherries ]
+    ingredients [ raisins ]";
+
+const E161: &str = "\
+E161: Entry already defined as an Object
+
+Definitions of the same entry are merged, so they must all be of the same
+kind. This entry was first defined as an Object.
+
+    lewis { country = \"United Kingdom\" }
+    lewis [ podium ]
+
+Redefine it as an Object. Its entries are merged, and its properties
+replace any with the same var:
+
+    lewis { country = \"United Kingdom\" }
+    lewis { quick = true }";
+
+const E162: &str = "\
+E162: Entry redefined with no new data
+
+An entry which already exists was written again without a structure, so
+the redefinition adds nothing.
+
+    atom
+    atom
+
+Remove the second definition. To add to an entry, redefine it with a
+structure of the same kind:
+
+    lewis { country = \"United Kingdom\" }
+    lewis { quick = true }";
+
+const E163: &str = "\
+E163: Expected '=' or ','
+
+A list of vars separated by ',' must end with '=' and a value.
+
+    evens, perfects
+
+Bind the vars to a value. Every var is bound to the whole value:
+
+    evens, perfects = (6, 28, 496, 8128)";
+
+const E164: &str = "\
+E164: Invalid syntax inside a Tuple
+
+The elements of a Tuple must be values separated by ','.
+
+    primes = (2 3 5)
+
+Separate the elements with ',':
+
+    primes = (2, 3, 5)";
+
+const E165: &str = "\
+E165: No such var
+
+A Var refers to a property which has not been bound at that point in the
+same structure. Vars are resolved as the document is read, so the property
+must be bound before it is referenced.
+
+    backup = $primary
+    primary = \"irc.libera.chat\"
+
+Bind the property first:
+
+    primary = \"irc.libera.chat\"
+    backup = $primary";
+
+const E200: &str = "\
+E200: Invalid schema
+
+A schema is written in JACL, as rules for the entries and properties of
+the documents it checks. Part of this schema is not a valid rule.
+
+    props {
+        port { type = Integer; minimum = 1 }
+    }
+
+The fields of a rule are type, required, default, min, max, min_length,
+max_length, one_of, pattern, elems, each_elem, entries, props, each_entry,
+each_prop and open:
+
+    props {
+        port { type = Integer; min = 1 }
+    }";
+
+const E201: &str = "\
+E201: Wrong type
+
+An entry or property is not of the type its schema gives it.
+
+    port = \"6667\"
+
+with the schema
+
+    props {
+        port = Integer
+    }
+
+Give it a value of the right type:
+
+    port = 6667";
+
+const E202: &str = "\
+E202: Missing entry or property
+
+The schema requires an entry or property which is not there.
+
+    freenode {
+        addr = \"chat.freenode.net\"
+    }
+
+with the schema
+
+    entries {
+        freenode {
+            props {
+                addr = String
+                port = Integer
+            }
+        }
+    }
+
+Add it:
+
+    freenode {
+        addr = \"chat.freenode.net\"
+        port = 6667
+    }
+
+If it should be optional, mark its rule with required = false, or give
+it a default.";
+
+const E203: &str = "\
+E203: Unexpected entry or property
+
+The schema lists the entries and properties a structure may hold, and
+this is not one of them.
+
+    nick = \"martin\"
+    colour = true
+
+with the schema
+
+    props {
+        nick = String
+    }
+
+Remove it, or correct its name if it is misspelt. To allow entries and
+properties which are not listed, mark the rule with open = true.";
+
+const E204: &str = "\
+E204: Number out of range
+
+A number is below the min or above the max of its rule.
+
+    port = 70000
+
+with the schema
+
+    props {
+        port { type = Integer; min = 1; max = 65535 }
+    }
+
+Use a number within the range:
+
+    port = 6667";
+
+const E205: &str = "\
+E205: Wrong length
+
+A String has too few or too many characters, or a Tuple too few or too
+many elements, for its rule. Its min_length and max_length give the
+bounds, and its elems give the exact number of elements of a Tuple.
+
+    position = (1.5, 2.5, 0.0)
+
+with the schema
+
+    props {
+        position { type = Tuple; elems = (Float, Float) }
+    }
+
+Give it the right number of elements:
+
+    position = (1.5, 2.5)";
+
+const E206: &str = "\
+E206: Value not allowed
+
+The rule lists the values allowed with one_of, and this is not one of
+them.
+
+    action = \"hide\"
+
+with the schema
+
+    props {
+        action { one_of = (ignore, highlight) }
+    }
+
+Use one of the values listed. A Key in one_of also allows a String with
+the same text:
+
+    action = \"ignore\"";
+
+const E207: &str = "\
+E207: String does not match pattern
+
+A String does not match the regular expression given by the pattern of
+its rule.
+
+    nick = \"martin!\"
+
+with the schema
+
+    props {
+        nick { type = String; pattern = \"^[a-z]+$\" }
+    }
+
+Change the String so it matches:
+
+    nick = \"martin\"";
@@ -0,0 +1,477 @@
+use crate::JaclError;
+use crate::cst::{SyntaxTree, SyntaxNode, SyntaxElement, SyntaxKind};
+
+/* Formatter
+ *
+ * The formatter lays a document out afresh from its syntax tree, so that
+ * only the choices in FormatConfig decide indentation and spacing. Each
+ * comment is kept with the item it belongs to: comments on their own
+ * lines lead the item below them, and a comment after an item on the
+ * same line trails it.
+ */
+
+/// How `format_string` lays out a document
+#[derive(Clone, Debug)]
+pub struct FormatConfig {
+    /// The text used for one level of indentation
+    pub indent: String,
+    /// Pad the vars of consecutive bindings so that their `=` line up
+    pub align_equals: bool,
+    /// Runs of blank lines longer than this are shortened
+    pub max_blank_lines: usize,
+    /// Separate items spanning several lines from their neighbours
+    /// with a blank line
+    pub blank_around_blocks: bool,
+    /// Tuples which would make a line longer than this are split,
+    /// one element per line
+    pub max_width: usize,
+    /// Move properties above the entries of each structure.
+    /// Properties bound to structures are treated as entries. A property
+    /// stays put where moving it would change what a var holds.
+    pub props_first: bool,
+    /// The number of columns a tab counts as when measuring lines
+    pub tab_width: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> FormatConfig {
+        FormatConfig {
+            indent: String::from("    "),
+            align_equals: false,
+            max_blank_lines: 1,
+            blank_around_blocks: false,
+            max_width: 80,
+            props_first: false,
+            tab_width: 4,
+        }
+    }
+}
+
+/// Format `input` according to `config`. Documents which do not parse are
+/// not formatted, and their errors are returned instead.
+pub fn format_string<'src>(input: &'src str, config: &FormatConfig) -> Result<String, JaclError<'src>> {
+    crate::read_string(input)?;
+    Ok(SyntaxTree::parse(input).format_valid(config))
+}
+
+impl SyntaxTree {
+    /// Lay the document out according to `config`
+    pub fn format(&self, config: &FormatConfig) -> Result<String, JaclError<'_>> {
+        self.lower()?;
+        Ok(self.format_valid(config))
+    }
+
+    fn format_valid(&self, config: &FormatConfig) -> String {
+        let fmt = Formatter { tree: self, config };
+        let layout = fmt.layout(self.root().children(), false, is_item);
+        let mut out = fmt.block(&layout, 0);
+        // Nothing but blank lines
+        if out.trim().is_empty() {
+            out.clear();
+        }
+        out
+    }
+}
+
+/* Collect Items */
+
+/// An item, along with the comments and blank lines belonging to it
+struct Unit<'t> {
+    /// Blank lines before the first line of the unit
+    gap: usize,
+    /// Comments on their own lines before the item, and the blank lines before each
+    leading: Vec<(usize, &'t str)>,
+    /// Blank lines between the last leading comment and the item
+    blanks: usize,
+    item: &'t SyntaxNode,
+    trailing: Option<&'t str>,
+}
+
+/// The contents of a structure or tuple, between its brackets
+struct Layout<'t> {
+    /// A comment on the same line as the opening bracket
+    open: Option<&'t str>,
+    units: Vec<Unit<'t>>,
+    /// Comments after the last item
+    dangling: Vec<(usize, &'t str)>,
+}
+
+impl Layout<'_> {
+    fn is_empty(&self) -> bool {
+        self.open.is_none() && self.units.is_empty() && self.dangling.is_empty()
+    }
+
+    fn has_comments(&self) -> bool {
+        self.open.is_some() || !self.dangling.is_empty()
+            || self.units.iter().any(|unit| !unit.leading.is_empty() || unit.trailing.is_some())
+    }
+}
+
+fn is_item(node: &SyntaxNode) -> bool {
+    matches!(node.kind(), SyntaxKind::Binding | SyntaxKind::Entry)
+}
+
+fn is_elem(node: &SyntaxNode) -> bool {
+    matches!(node.kind(), SyntaxKind::Value | SyntaxKind::Var | SyntaxKind::Tuple)
+}
+
+/// The contents of a bracketed node, without its brackets
+fn inner(node: &SyntaxNode) -> &[SyntaxElement] {
+    let children = node.children();
+    let end = match children.last() {
+        Some(SyntaxElement::Token(tok)) if children.len() > 1 &&
+            matches!(tok.kind(), SyntaxKind::RBrace | SyntaxKind::RBrack |
+                                 SyntaxKind::RBracePct | SyntaxKind::RParen) => children.len() - 1,
+        _ => children.len(),
+    };
+    &children[1..end]
+}
+
+/// Does the item span several lines?
+fn multiline(text: &str) -> bool {
+    text.contains('\n')
+}
+
+struct Formatter<'t> {
+    tree: &'t SyntaxTree,
+    config: &'t FormatConfig,
+}
+
+impl<'t> Formatter<'t> {
+    fn text(&self, elem: &SyntaxElement) -> &'t str {
+        match elem {
+            SyntaxElement::Token(tok) => tok.text(self.tree),
+            SyntaxElement::Node(node) => node.text(self.tree),
+        }
+    }
+
+    /// Group `elems` into units, attaching comments to items
+    fn layout(&self, elems: &'t [SyntaxElement], open_line: bool, is_item: fn(&SyntaxNode) -> bool) -> Layout<'t> {
+        let mut layout = Layout { open: None, units: Vec::new(), dangling: Vec::new() };
+        let mut pending: Vec<(usize, &'t str)> = Vec::new();
+        let mut blanks = 0;
+        let mut open_line = open_line;
+        let mut line_used = open_line;
+        let mut last_on_line: Option<usize> = None;
+
+        for elem in elems {
+            match elem {
+                SyntaxElement::Token(tok) => match tok.kind() {
+                    SyntaxKind::Whitespace => {},
+                    SyntaxKind::Comment => {
+                        let comment = tok.text(self.tree).trim_end();
+                        if let Some(idx) = last_on_line {
+                            layout.units[idx].trailing = Some(comment);
+                        }
+                        else if open_line {
+                            layout.open = Some(comment);
+                        }
+                        else {
+                            pending.push((blanks, comment));
+                            blanks = 0;
+                        }
+                        line_used = true;
+                    },
+                    SyntaxKind::Break if self.text(elem) == "\n" => {
+                        if !line_used {
+                            blanks += 1;
+                        }
+                        line_used = false;
+                        last_on_line = None;
+                        open_line = false;
+                    },
+                    _ => {
+                        line_used = true;
+                    },
+                },
+                SyntaxElement::Node(node) if is_item(node) => {
+                    let (gap, before) = match pending.first_mut() {
+                        Some(first) => (std::mem::take(&mut first.0), blanks),
+                        None => (blanks, 0),
+                    };
+                    layout.units.push(Unit {
+                        gap,
+                        leading: std::mem::take(&mut pending),
+                        blanks: before,
+                        item: node,
+                        trailing: None,
+                    });
+                    blanks = 0;
+                    line_used = true;
+                    open_line = false;
+                    last_on_line = Some(layout.units.len() - 1);
+                },
+                SyntaxElement::Node(_) => {
+                    line_used = true;
+                },
+            }
+        }
+        layout.dangling = pending;
+        layout
+    }
+
+    fn indent(&self, depth: usize) -> String {
+        self.config.indent.repeat(depth)
+    }
+
+    fn width(&self, text: &str) -> usize {
+        text.chars().map(|c| if c == '\t' { self.config.tab_width } else { 1 }).sum()
+    }
+
+    fn blank(&self, blanks: usize) -> usize {
+        usize::min(blanks, self.config.max_blank_lines)
+    }
+
+    /// Lay out the units of a structure, one per line, at `depth`
+    fn block(&self, layout: &Layout<'t>, depth: usize) -> String {
+        let indent = self.indent(depth);
+        let mut units = layout.units.iter().collect::<Vec<&Unit>>();
+        if self.config.props_first {
+            units = self.props_first(units);
+        }
+
+        // Render the items before laying them out, to know which span several lines
+        let items = units.iter()
+                         .map(|unit| self.item(unit.item, depth))
+                         .collect::<Vec<(Option<String>, String)>>();
+        let mut gaps = Vec::new();
+        for (idx, unit) in units.iter().enumerate() {
+            let mut gap = if idx == 0 { 0 } else { self.blank(unit.gap) };
+            if idx > 0 && self.config.blank_around_blocks
+                    && (multiline(&items[idx].1) || multiline(&items[idx - 1].1)) {
+                gap = usize::max(gap, 1);
+            }
+            gaps.push(gap);
+        }
+
+        // Bindings on consecutive lines are aligned together
+        let mut pads = vec![0; units.len()];
+        if self.config.align_equals {
+            let mut start = 0;
+            while start < units.len() {
+                let mut end = start;
+                while end < units.len() && items[end].0.is_some() && !multiline(&items[end].1)
+                        && (end == start || gaps[end] == 0) {
+                    end += 1;
+                }
+                let width = items[start..end].iter()
+                                             .filter_map(|item| item.0.as_ref())
+                                             .map(|names| names.chars().count())
+                                             .max().unwrap_or(0);
+                for pad in pads.iter_mut().take(end).skip(start) {
+                    *pad = width;
+                }
+                start = usize::max(end, start + 1);
+            }
+        }
+
+        let mut out = String::new();
+        for (idx, unit) in units.iter().enumerate() {
+            out.push_str(&"\n".repeat(gaps[idx]));
+            for (blanks, comment) in &unit.leading {
+                out.push_str(&"\n".repeat(self.blank(*blanks)));
+                out.push_str(&format!("{}{}\n", indent, comment));
+            }
+            if !unit.leading.is_empty() {
+                out.push_str(&"\n".repeat(self.blank(unit.blanks)));
+            }
+            out.push_str(&indent);
+            match &items[idx] {
+                (Some(names), value) => {
+                    out.push_str(&format!("{:<width$} = {}", names, value, width = pads[idx]));
+                },
+                (None, text) => out.push_str(text),
+            }
+            if let Some(comment) = unit.trailing {
+                out.push_str(&format!(" {}", comment));
+            }
+            out.push('\n');
+        }
+        for (idx, (blanks, comment)) in layout.dangling.iter().enumerate() {
+            if idx > 0 || !units.is_empty() {
+                out.push_str(&"\n".repeat(self.blank(*blanks)));
+            }
+            out.push_str(&format!("{}{}\n", indent, comment));
+        }
+        out
+    }
+
+    /// Move properties above the other items, keeping the order within
+    /// each group. A property is left in place if it would pass an item
+    /// binding one of the same vars, or an item it refers to or which
+    /// refers to it by `$var`.
+    fn props_first<'u>(&self, units: Vec<&'u Unit<'t>>) -> Vec<&'u Unit<'t>> {
+        let mut props = Vec::new();
+        let mut rest: Vec<&Unit> = Vec::new();
+        for unit in units {
+            let vars = self.vars(unit.item);
+            let uses = self.uses(unit.item);
+            let blocked = rest.iter().any(|other| {
+                let other_vars = self.vars(other.item);
+                other_vars.iter().any(|var| vars.contains(var) || uses.contains(var))
+                    || self.uses(other.item).iter().any(|var| vars.contains(var))
+            });
+            if self.is_prop(unit.item) && !blocked {
+                props.push(unit);
+            }
+            else {
+                rest.push(unit);
+            }
+        }
+        props.extend(rest);
+        props
+    }
+
+    /// The vars an item binds
+    fn vars(&self, item: &SyntaxNode) -> Vec<&'t str> {
+        if item.kind() != SyntaxKind::Binding {
+            return Vec::new();
+        }
+        item.children().iter()
+            .filter(|child| child.kind() == SyntaxKind::Name)
+            .map(|child| self.text(child))
+            .collect()
+    }
+
+    /// The vars a binding copies with `$var`. Those within a structure
+    /// refer to its own vars, so are not counted.
+    fn uses(&self, item: &SyntaxNode) -> Vec<&'t str> {
+        if !self.is_prop(item) {
+            return Vec::new();
+        }
+        let toks = item.tokens().into_iter()
+                       .filter(|tok| !tok.kind().is_trivia())
+                       .collect::<Vec<_>>();
+        toks.windows(2)
+            .filter(|pair| pair[0].kind() == SyntaxKind::Dollar && pair[1].kind() == SyntaxKind::Name)
+            .map(|pair| pair[1].text(self.tree))
+            .collect()
+    }
+
+    /// Is this a binding to a value, rather than to a structure?
+    fn is_prop(&self, item: &SyntaxNode) -> bool {
+        item.kind() == SyntaxKind::Binding
+            && !item.child_nodes().iter().any(|node| node.kind().is_struct())
+    }
+
+    /// Render an item. Bindings are returned as their vars and their value,
+    /// so that they can be aligned.
+    fn item(&self, item: &SyntaxNode, depth: usize) -> (Option<String>, String) {
+        let names = item.children().iter()
+                        .filter(|child| child.kind() == SyntaxKind::Name)
+                        .map(|child| self.text(child))
+                        .collect::<Vec<&str>>();
+        let node = item.child_nodes().into_iter().last();
+        match item.kind() {
+            SyntaxKind::Binding => {
+                let names = names.join(", ");
+                let used = self.width(&self.indent(depth)) + names.chars().count() + 3;
+                let value = match node {
+                    Some(node) if node.kind().is_struct() => self.strct(node, depth),
+                    Some(node) => self.value(node, depth, used),
+                    None => String::new(),
+                };
+                (Some(names), value)
+            },
+            _ => {
+                let mut text = names.join(" + ");
+                if let Some(node) = node {
+                    if !text.is_empty() {
+                        text.push(' ');
+                    }
+                    text.push_str(&self.strct(node, depth));
+                }
+                (None, text)
+            },
+        }
+    }
+
+    fn strct(&self, node: &SyntaxNode, depth: usize) -> String {
+        let (open, close) = match node.kind() {
+            SyntaxKind::Table => ("[", "]"),
+            SyntaxKind::Map => ("{%", "%}"),
+            _ => ("{", "}"),
+        };
+        let layout = self.layout(inner(node), true, is_item);
+        if layout.is_empty() {
+            return match node.kind() {
+                SyntaxKind::Map => String::from("{% %}"),
+                _ => format!("{}{}", open, close),
+            };
+        }
+        let mut out = String::from(open);
+        if let Some(comment) = layout.open {
+            out.push_str(&format!(" {}", comment));
+        }
+        out.push('\n');
+        out.push_str(&self.block(&layout, depth + 1));
+        out.push_str(&self.indent(depth));
+        out.push_str(close);
+        out
+    }
+
+    /// Render a value, given the width of the line before it
+    fn value(&self, node: &SyntaxNode, depth: usize, used: usize) -> String {
+        match node.kind() {
+            SyntaxKind::Tuple => {
+                let layout = self.layout(inner(node), true, is_elem);
+                match self.flat(node) {
+                    Some(flat) if used + flat.chars().count() <= self.config.max_width => flat,
+                    _ => self.wrapped(&layout, depth),
+                }
+            },
+            _ => {
+                node.tokens().iter()
+                    .filter(|tok| !tok.kind().is_trivia())
+                    .map(|tok| tok.text(self.tree))
+                    .collect::<String>()
+            },
+        }
+    }
+
+    /// Render a value on a single line, if it contains no comments
+    fn flat(&self, node: &SyntaxNode) -> Option<String> {
+        if node.kind() != SyntaxKind::Tuple {
+            return Some(self.value(node, 0, 0));
+        }
+        let layout = self.layout(inner(node), true, is_elem);
+        if layout.has_comments() {
+            return None;
+        }
+        let elems = layout.units.iter()
+                          .map(|unit| self.flat(unit.item))
+                          .collect::<Option<Vec<String>>>()?;
+        Some(format!("({})", elems.join(", ")))
+    }
+
+    /// Render a tuple with each of its elements on its own line
+    fn wrapped(&self, layout: &Layout<'t>, depth: usize) -> String {
+        let indent = self.indent(depth + 1);
+        let mut out = String::from("(");
+        if let Some(comment) = layout.open {
+            out.push_str(&format!(" {}", comment));
+        }
+        out.push('\n');
+        for (idx, unit) in layout.units.iter().enumerate() {
+            for (_, comment) in &unit.leading {
+                out.push_str(&format!("{}{}\n", indent, comment));
+            }
+            let used = self.width(&indent);
+            out.push_str(&indent);
+            out.push_str(&self.value(unit.item, depth + 1, used));
+            if idx + 1 < layout.units.len() {
+                out.push(',');
+            }
+            if let Some(comment) = unit.trailing {
+                out.push_str(&format!(" {}", comment));
+            }
+            out.push('\n');
+        }
+        for (_, comment) in &layout.dangling {
+            out.push_str(&format!("{}{}\n", indent, comment));
+        }
+        out.push_str(&self.indent(depth));
+        out.push(')');
+        out
+    }
+}
@@ -0,0 +1,392 @@
+use indexmap::map::IndexMap;
+use indexmap::set::IndexSet;
+
+use crate::Jacl;
+use crate::schema::{entries_of, props_of, is_bound};
+use crate::types::{Struct, Value};
+use crate::writer::write_value;
+
+/* Inferring Schemas
+ *
+ * Everything found at the same place in the documents is covered by one
+ * rule: the root of every document, the `servers` entry of every root, and
+ * so on. The entries of a Table, and anonymous entries, share the rule
+ * `each_entry`. So do the entries of an Object which seem to be items
+ * keyed by name, like the servers in `servers`, rather than the fields of
+ * a record: structures of the same kind which mostly hold the same names.
+ * A property holding an anonymous structure is given the rule for its
+ * contents.
+ *
+ * An entry or property is required if it is in every structure where it
+ * was found at all. A String or Key is limited to one_of the values seen
+ * when there are few of them, each seen twice on average. A Tuple whose
+ * elements are all of the same type has the rule `each_elem`, and one
+ * whose elements differ in type, but which is always the same length, has
+ * `elems`.
+ *
+ * The rules allow little beyond what was seen, so they are a starting
+ * point to be loosened or tightened by hand.
+ */
+
+/// The most values a String or Key may take for them to be listed in one_of
+const MAX_CHOICES: usize = 8;
+
+const INDENT: &str = "    ";
+
+/// Everything seen at one place in the documents
+#[derive(Default)]
+struct Shape {
+    /// How many items were seen
+    seen: usize,
+    /// The names of their types, in the order they were first seen
+    types: Vec<&'static str>,
+    /// How many of them were Strings or Keys, and the distinct values of
+    /// those, up to one more than MAX_CHOICES
+    named: usize,
+    choices: Vec<Value>,
+    /// The distinct lengths of the Tuples
+    lengths: Vec<usize>,
+    /// The elements of the Tuples at each index
+    elems: Vec<Shape>,
+    /// Every element of the Tuples
+    each_elem: Option<Box<Shape>>,
+    /// How many of them were structures, and what those held
+    structs: usize,
+    entries: IndexMap<String, Shape>,
+    props: IndexMap<String, Shape>,
+    each_entry: Option<Box<Shape>>,
+}
+
+fn kind_name(strct: &Struct) -> &'static str {
+    match strct {
+        Struct::Object { .. } => "Object",
+        Struct::Table { .. } => "Table",
+        Struct::Map { .. } => "Map",
+    }
+}
+
+impl Shape {
+    fn saw(&mut self, ty: &'static str) {
+        if !self.types.contains(&ty) {
+            self.types.push(ty);
+        }
+    }
+
+    fn choice(&mut self, val: &Value) {
+        self.named += 1;
+        if self.choices.len() <= MAX_CHOICES && !self.choices.contains(val) {
+            self.choices.push(val.clone());
+        }
+    }
+
+    fn entry(&mut self, entry: &Option<Struct>) {
+        self.seen += 1;
+        match entry {
+            Some(strct) => self.structure(strct),
+            None => self.saw("Empty"),
+        }
+    }
+
+    fn structure(&mut self, strct: &Struct) {
+        self.saw(kind_name(strct));
+        self.structs += 1;
+
+        for (key, entry) in entries_of(strct).into_iter().flatten() {
+            let anonymous = key.starts_with('#');
+            // Anonymous entries bound to properties are seen as their values
+            if anonymous && is_bound(strct, key) {
+                continue;
+            }
+            let shape = if anonymous || matches!(strct, Struct::Table { .. }) {
+                self.each_entry.get_or_insert_with(Default::default)
+            }
+            else {
+                self.entries.entry(key.clone()).or_default()
+            };
+            shape.entry(entry);
+        }
+        for (name, val) in props_of(strct).into_iter().flatten() {
+            self.props.entry(name.clone()).or_default().value(val, strct);
+        }
+    }
+
+    fn value(&mut self, val: &Value, parent: &Struct) {
+        self.seen += 1;
+        match val {
+            Value::Key(key) => match entries_of(parent).and_then(|entries| entries.get(key)) {
+                Some(Some(strct)) if key.starts_with('#') => self.structure(strct),
+                Some(Some(strct)) => self.saw(kind_name(strct)),
+                Some(None) => self.saw("Empty"),
+                None => {
+                    self.saw("Key");
+                    self.choice(val);
+                },
+            },
+            Value::String(_) => {
+                self.saw("String");
+                self.choice(val);
+            },
+            Value::Integer(_) => self.saw("Integer"),
+            Value::Float(_) => self.saw("Float"),
+            Value::Boolean(_) => self.saw("Boolean"),
+            Value::Tuple(vals) => {
+                self.saw("Tuple");
+                if !self.lengths.contains(&vals.len()) {
+                    self.lengths.push(vals.len());
+                }
+                for (idx, val) in vals.iter().enumerate() {
+                    if self.elems.len() <= idx {
+                        self.elems.push(Shape::default());
+                    }
+                    self.elems[idx].value(val, parent);
+                    self.each_elem.get_or_insert_with(Default::default).value(val, parent);
+                }
+            },
+        }
+    }
+
+    /// Add everything seen in `other`, as though it had been seen here
+    fn merge(&mut self, other: &Shape) {
+        self.seen += other.seen;
+        for ty in &other.types {
+            self.saw(ty);
+        }
+        self.named += other.named;
+        for val in &other.choices {
+            if self.choices.len() <= MAX_CHOICES && !self.choices.contains(val) {
+                self.choices.push(val.clone());
+            }
+        }
+        for len in &other.lengths {
+            if !self.lengths.contains(len) {
+                self.lengths.push(*len);
+            }
+        }
+        for (idx, elem) in other.elems.iter().enumerate() {
+            if self.elems.len() <= idx {
+                self.elems.push(Shape::default());
+            }
+            self.elems[idx].merge(elem);
+        }
+        if let Some(elem) = &other.each_elem {
+            self.each_elem.get_or_insert_with(Default::default).merge(elem);
+        }
+        self.structs += other.structs;
+        for (key, entry) in &other.entries {
+            self.entries.entry(key.clone()).or_default().merge(entry);
+        }
+        for (name, prop) in &other.props {
+            self.props.entry(name.clone()).or_default().merge(prop);
+        }
+        if let Some(entry) = &other.each_entry {
+            self.each_entry.get_or_insert_with(Default::default).merge(entry);
+        }
+    }
+
+    /// Do the named entries seem to be items keyed by name? They must be
+    /// structures of one kind, with at least half of the names within them
+    /// common to all.
+    fn is_collection(&self) -> bool {
+        let first = match self.entries.values().next() {
+            Some(first) if self.entries.len() >= 2 => first.type_names(),
+            _ => return false,
+        };
+        if !matches!(first.as_slice(), ["Object"] | ["Table"] | ["Map"]) ||
+           !self.entries.values().all(|entry| entry.type_names() == first) {
+            return false;
+        }
+
+        let names = |entry: &Shape| entry.entries.keys().chain(entry.props.keys()).cloned().collect::<IndexSet<String>>();
+        let mut all = IndexSet::new();
+        let mut common: Option<IndexSet<String>> = None;
+        for entry in self.entries.values() {
+            let names = names(entry);
+            all.extend(names.iter().cloned());
+            common = Some(match common {
+                Some(common) => common.intersection(&names).cloned().collect(),
+                None => names,
+            });
+        }
+        common.map_or(0, |common| common.len()) * 2 >= all.len()
+    }
+
+    /// The types seen, leaving out Integers if there were Floats, since
+    /// Integers are accepted as Floats
+    fn type_names(&self) -> Vec<&'static str> {
+        let floats = self.types.contains(&"Float");
+        self.types.iter().copied().filter(|ty| !(floats && *ty == "Integer")).collect()
+    }
+
+    /// The type of the items, or a Tuple of the alternatives
+    fn type_value(&self) -> String {
+        match self.type_names().as_slice() {
+            [] => String::from("Any"),
+            [ty] => ty.to_string(),
+            types => format!("({})", types.join(", ")),
+        }
+    }
+
+    /// The values the items were limited to, if they seem to be limited
+    fn choices(&self) -> Option<&[Value]> {
+        let distinct = self.choices.len();
+        if self.named == self.seen && (2..=MAX_CHOICES).contains(&distinct) && self.seen >= 2 * distinct {
+            Some(&self.choices)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Does the rule need to say what the items hold? Values must all be
+    /// anonymous structures for their contents to be checked.
+    fn has_contents(&self, value: bool) -> bool {
+        let structs = if value { self.structs == self.seen } else { self.structs > 0 };
+        structs && (!self.entries.is_empty() || !self.props.is_empty() || self.each_entry.is_some())
+    }
+
+    /// The rule for the elements of the Tuples, if there were any
+    fn elem_rule(&self) -> Option<ElemRule<'_>> {
+        let each_elem = self.each_elem.as_deref()?;
+        let first = self.elems.first().map(Shape::type_names);
+        let same_types = self.elems.iter().all(|elem| Some(elem.type_names()) == first);
+        if !same_types && self.lengths.len() == 1 {
+            Some(ElemRule::Elems(self.elems.iter().map(Shape::type_value).collect()))
+        }
+        else {
+            Some(ElemRule::EachElem(each_elem))
+        }
+    }
+
+    /// Can the rule be written as just its type?
+    fn is_simple(&self, value: bool) -> bool {
+        self.choices().is_none() && self.elem_rule().is_none() && !self.has_contents(value)
+    }
+}
+
+enum ElemRule<'s> {
+    /// The type of the element at each index
+    Elems(Vec<String>),
+    EachElem(&'s Shape),
+}
+
+/// Where a rule is written, which decides what it needs to say
+#[derive(Clone, Copy)]
+enum Site {
+    /// An entry or property, in how many structures it might have been found
+    Field { value: bool, within: usize },
+    /// `each_entry` or `each_elem`
+    Each { value: bool },
+}
+
+struct Writer {
+    out: String,
+}
+
+impl Writer {
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// The entries, properties and each_entry of a rule for structures
+    fn contents(&mut self, depth: usize, shape: &Shape) {
+        // The items of a collection share each_entry with anonymous entries
+        let items = if shape.is_collection() {
+            let mut each_entry = Shape::default();
+            for entry in shape.entries.values().chain(shape.each_entry.as_deref()) {
+                each_entry.merge(entry);
+            }
+            Some(each_entry)
+        }
+        else {
+            if !shape.entries.is_empty() {
+                self.line(depth, "entries {");
+                for (key, entry) in &shape.entries {
+                    self.rule(depth + 1, key, entry, Site::Field { value: false, within: shape.structs });
+                }
+                self.line(depth, "}");
+            }
+            None
+        };
+        if !shape.props.is_empty() {
+            self.line(depth, "props {");
+            for (name, prop) in &shape.props {
+                self.rule(depth + 1, name, prop, Site::Field { value: true, within: shape.structs });
+            }
+            self.line(depth, "}");
+        }
+        if let Some(each_entry) = items.as_ref().or(shape.each_entry.as_deref()) {
+            self.rule(depth, "each_entry", each_entry, Site::Each { value: false });
+        }
+    }
+
+    /// The rule for `shape`, bound to `name`
+    fn rule(&mut self, depth: usize, name: &str, shape: &Shape, site: Site) {
+        let mut fields = vec![format!("type = {}", shape.type_value())];
+        let value = match site {
+            Site::Field { value, within } => {
+                if shape.seen < within {
+                    fields.push(String::from("required = false"));
+                }
+                value
+            },
+            Site::Each { value } => value,
+        };
+
+        if let Some(choices) = shape.choices() {
+            let choices = choices.iter().map(|val| write_value(val).unwrap_or_default()).collect::<Vec<String>>();
+            fields.push(format!("one_of = ({})", choices.join(", ")));
+        }
+
+        let mut each_elem = None;
+        match shape.elem_rule() {
+            Some(ElemRule::Elems(types)) => fields.push(format!("elems = ({})", types.join(", "))),
+            Some(ElemRule::EachElem(elem)) if elem.is_simple(true) => fields.push(format!("each_elem = {}", elem.type_value())),
+            Some(ElemRule::EachElem(elem)) => each_elem = Some(elem),
+            None => {},
+        }
+
+        let contents = shape.has_contents(value);
+        if fields.len() == 1 && each_elem.is_none() && !contents {
+            self.line(depth, &format!("{} = {}", name, shape.type_value()));
+        }
+        else if each_elem.is_none() && !contents {
+            self.line(depth, &format!("{} {{ {} }}", name, fields.join("; ")));
+        }
+        else {
+            self.line(depth, &format!("{} {{", name));
+            for field in &fields {
+                self.line(depth + 1, field);
+            }
+            if let Some(elem) = each_elem {
+                self.rule(depth + 1, "each_elem", elem, Site::Each { value: true });
+            }
+            if contents {
+                self.contents(depth + 1, shape);
+            }
+            self.line(depth, "}");
+        }
+    }
+}
+
+/// Write a schema which every one of `docs` follows, to be refined by hand.
+///
+/// Each rule allows only the types seen where it applies, requires what
+/// was always present, and limits Strings and Keys to the values seen if
+/// they seem to be chosen from a few.
+pub fn infer_schema<'d, I: IntoIterator<Item = &'d Jacl>>(docs: I) -> String {
+    let mut root = Shape::default();
+    for doc in docs {
+        root.seen += 1;
+        root.structure(doc.data());
+    }
+
+    let mut writer = Writer { out: String::new() };
+    let noun = if root.seen == 1 { "document" } else { "documents" };
+    writer.line(0, &format!("# Inferred from {} {}", root.seen, noun));
+    writer.contents(0, &root);
+    writer.out
+}
@@ -2,27 +2,81 @@ mod util;
 mod tokeniser;
 mod parser;
 mod error;
+mod render;
+mod diagnostic;
+mod explain;
 mod types;
 mod api;
+mod cst;
+mod path;
+mod query;
+mod visit;
+mod schema;
+mod infer;
+mod writer;
+mod edit;
+mod format;
+mod dom;
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "serde")]
+mod ser;
 
-pub use error::{Error};
+pub use error::{Error, ErrorCode, Severity, Label, Suggestion};
+pub use render::RenderConfig;
+pub use explain::explain;
 pub use crate::api::*;
+pub use crate::cst::{SyntaxTree, SyntaxNode, SyntaxToken, SyntaxElement, SyntaxKind};
+pub use crate::edit::EditError;
+pub use crate::format::{FormatConfig, format_string};
+pub use crate::dom::{Structure, StructureBuilder, DomError};
+#[cfg(feature = "serde")]
+pub use crate::de::{from_str, from_jacl, DeError};
+#[cfg(feature = "serde")]
+pub use crate::ser::{to_string, to_string_pretty, to_jacl, SerError};
+pub use crate::writer::WriteError;
+pub use crate::path::{Segment, Item, PathError, PathErrorKind, format_path};
+pub use crate::query::{Query, QueryError, Match, Matches};
+pub use crate::visit::{Visitor, VisitorMut};
+pub use crate::schema::{Schema, Violation};
+pub use crate::infer::infer_schema;
 
 type Lines = Vec<(usize, usize)>;
 
-pub fn read_string<'src>(input: &'src str) -> Result<Jacl, JaclError> {
-    match tokeniser::tokenise(&input) {
-        (lines, Ok(toks)) => {
-            match parser::parse(&input, &lines, toks) {
-                Ok(data) => Ok(Jacl::init(data)),
-                Err(err) => Err(JaclError::from_error(err, &input, lines)),
-            }
-        },
-        (lines, Err(errors)) => {
-            Err(JaclError::from_error(
-                    errors.get(0).expect("Tokeniser returned empty error list").clone(),
-                    &input,
-                    lines))
-        }
+pub fn read_string(input: &str) -> Result<Jacl, JaclError<'_>> {
+    match read_string_partial(input) {
+        (jacl, None) => Ok(jacl),
+        (_, Some(err)) => Err(err),
+    }
+}
+
+/// Read as much of `input` as possible, even if it contains errors.
+///
+/// The returned Jacl holds every definition that could be parsed. If the
+/// input was malformed, the errors are returned alongside it. When there
+/// are lexical errors, parser errors are not reported, since they are
+/// likely to be knock-on effects of the lexical ones.
+pub fn read_string_partial(input: &str) -> (Jacl, Option<JaclError<'_>>) {
+    let (lines, toks, lex_errors) = tokeniser::tokenise(input);
+    build(input, lines, toks, lex_errors)
+}
+
+/// Write `jacl` back out as JACL text, which `read_string` will read
+/// as an equal document. Comments and layout are not kept.
+pub fn write_string(jacl: &Jacl) -> Result<String, WriteError> {
+    writer::write_document(jacl.data(), true)
+}
+
+pub(crate) fn build<'src>(input: &'src str, lines: Lines,
+               toks: Vec<tokeniser::Token<'src>>,
+               lex_errors: Vec<Error>) -> (Jacl, Option<JaclError<'src>>) {
+    let (data, parse_errors) = parser::parse(input, &lines, toks);
+    let errors = if !lex_errors.is_empty() { lex_errors } else { parse_errors };
+    let jacl = Jacl::init(data, input, lines.clone());
+    if errors.is_empty() {
+        (jacl, None)
+    }
+    else {
+        (jacl, Some(JaclError::from_errors(errors, input, lines)))
     }
 }
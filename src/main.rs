@@ -0,0 +1,322 @@
+use std::io::{IsTerminal, Read};
+use std::process;
+
+use jacl::{FormatConfig, JaclError, RenderConfig};
+
+const USAGE: &str = "\
+Usage: jacl <command> [options] [files]
+
+Commands:
+    fmt     Format JACL documents
+    query   Print everything a query selects, as in
+            jacl query 'servers.*.port' config.jacl
+    check   Report any errors in JACL documents
+    validate
+            Check JACL documents against a schema, as in
+            jacl validate schema.jacl config.jacl
+    infer   Write a schema which JACL documents follow, as in
+            jacl infer old.jacl new.jacl > schema.jacl
+    explain Describe an error code in detail, as in
+            jacl explain E162
+
+Options for fmt:
+    --indent <n>            Indent with n spaces (default 4)
+    --tabs                  Indent with tabs
+    --align                 Align the '=' of consecutive bindings
+    --max-blank-lines <n>   Shorten runs of blank lines (default 1)
+    --blank-around-blocks   Put blank lines around multi-line items
+    --max-width <n>         Split tuples on longer lines (default 80)
+    --tab-width <n>         Count a tab as n columns (default 4)
+    --props-first           Move properties above entries
+    --write                 Rewrite the files in place
+    --check                 Only report files which are not formatted
+
+Options for query:
+    --paths                 Print only the path of each match
+
+query exits with status 1 when nothing matches.
+
+Options for check and validate:
+    --json                  Print each error as a line of JSON on stdout
+
+With no files, commands read from stdin, and fmt writes to stdout.";
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}\n\n{}", msg, USAGE);
+    process::exit(2);
+}
+
+fn number(args: &mut impl Iterator<Item = String>, flag: &str) -> usize {
+    match args.next().map(|arg| arg.parse::<usize>()) {
+        Some(Ok(num)) => num,
+        _ => fail(&format!("{} expects a number", flag)),
+    }
+}
+
+fn read(path: Option<&str>) -> String {
+    let result = match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut buf = String::new();
+            std::io::stdin().read_to_string(&mut buf).map(|_| buf)
+        },
+    };
+    result.unwrap_or_else(|err| {
+        eprintln!("Could not read {}: {}", path.unwrap_or("stdin"), err);
+        process::exit(1);
+    })
+}
+
+/// Print an error for `path`, in colour if stderr is a terminal
+fn report(path: Option<&str>, err: &JaclError<'_>) {
+    let config = RenderConfig {
+        color: std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        context: 1,
+        filename: Some(path.unwrap_or("stdin").to_string()),
+        ..RenderConfig::default()
+    };
+    eprintln!("{}", err.render_with(&config));
+}
+
+/// Print an error for `path`, as a line of JSON on stdout if `json` is set
+fn report_as(json: bool, path: Option<&str>, err: &JaclError<'_>) {
+    if json {
+        print!("{}", err.to_json_lines(Some(path.unwrap_or("stdin"))));
+    }
+    else {
+        report(path, err);
+    }
+}
+
+fn fmt(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut config = FormatConfig::default();
+    let mut write = false;
+    let mut check = false;
+    let mut files = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--indent" => config.indent = " ".repeat(number(&mut args, "--indent")),
+            "--tabs" => config.indent = String::from("\t"),
+            "--align" => config.align_equals = true,
+            "--max-blank-lines" => config.max_blank_lines = number(&mut args, "--max-blank-lines"),
+            "--blank-around-blocks" => config.blank_around_blocks = true,
+            "--max-width" => config.max_width = number(&mut args, "--max-width"),
+            "--tab-width" => config.tab_width = number(&mut args, "--tab-width"),
+            "--props-first" => config.props_first = true,
+            "--write" => write = true,
+            "--check" => check = true,
+            _ if arg.starts_with("--") => fail(&format!("Unknown option {}", arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        let input = read(None);
+        return match jacl::format_string(&input, &config) {
+            Ok(out) if check => if out == input { 0 } else { eprintln!("stdin is not formatted"); 1 },
+            Ok(out) => { print!("{}", out); 0 },
+            Err(err) => { report(None, &err); 1 },
+        };
+    }
+
+    let mut status = 0;
+    for path in &files {
+        let input = read(Some(path));
+        match jacl::format_string(&input, &config) {
+            Ok(out) if check => {
+                if out != input {
+                    println!("{}", path);
+                    status = 1;
+                }
+            },
+            Ok(out) if write => {
+                if out != input {
+                    if let Err(err) = std::fs::write(path, out) {
+                        eprintln!("Could not write {}: {}", path, err);
+                        status = 1;
+                    }
+                }
+            },
+            Ok(out) => print!("{}", out),
+            Err(err) => {
+                report(Some(path), &err);
+                status = 1;
+            },
+        }
+    }
+    status
+}
+
+fn query(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut paths = false;
+    let mut query = None;
+    let mut files = Vec::new();
+
+    for arg in args.by_ref() {
+        match arg.as_str() {
+            "--paths" => paths = true,
+            _ if arg.starts_with("--") => fail(&format!("Unknown option {}", arg)),
+            _ if query.is_none() => query = Some(arg),
+            _ => files.push(arg),
+        }
+    }
+
+    let query = match query.map(|query| query.parse::<jacl::Query>()) {
+        Some(Ok(query)) => query,
+        Some(Err(err)) => { eprintln!("{}", err); return 2; },
+        None => fail("query expects a query"),
+    };
+
+    let sources = if files.is_empty() { vec![None] } else { files.iter().map(|path| Some(path.as_str())).collect() };
+    let mut found = false;
+    let mut status = 0;
+    for path in sources {
+        let input = read(path);
+        let doc = match jacl::read_string(&input) {
+            Ok(doc) => doc,
+            Err(err) => {
+                report(path, &err);
+                status = 1;
+                continue;
+            },
+        };
+        let prefix = match path {
+            Some(path) if files.len() > 1 => format!("{}: ", path),
+            _ => String::new(),
+        };
+        for m in query.matches(&doc) {
+            found = true;
+            let text = m.item.to_jacl_string().unwrap_or_else(|err| err.to_string());
+            if paths || text.is_empty() {
+                println!("{}{}", prefix, m.path_string());
+            }
+            else {
+                println!("{}{} = {}", prefix, m.path_string(), text);
+            }
+        }
+    }
+    if status == 0 && !found { 1 } else { status }
+}
+
+fn check(args: impl Iterator<Item = String>) -> i32 {
+    let mut json = false;
+    let mut files = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if arg.starts_with("--") => fail(&format!("Unknown option {}", arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    let sources = if files.is_empty() { vec![None] } else { files.iter().map(|path| Some(path.as_str())).collect() };
+    let mut status = 0;
+    for path in sources {
+        let input = read(path);
+        if let Err(err) = jacl::read_string(&input) {
+            report_as(json, path, &err);
+            status = 1;
+        }
+    }
+    status
+}
+
+fn validate(args: impl Iterator<Item = String>) -> i32 {
+    let mut json = false;
+    let mut schema = None;
+    let mut files = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if arg.starts_with("--") => fail(&format!("Unknown option {}", arg)),
+            _ if schema.is_none() => schema = Some(arg),
+            _ => files.push(arg),
+        }
+    }
+
+    let schema_path = schema.unwrap_or_else(|| fail("validate expects a schema"));
+    let schema_input = read(Some(&schema_path));
+    let schema = match jacl::read_string(&schema_input) {
+        Ok(doc) => match jacl::Schema::from_jacl(&doc) {
+            Ok(schema) => schema,
+            Err(err) => { report(Some(&schema_path), &err); return 2; },
+        },
+        Err(err) => { report(Some(&schema_path), &err); return 2; },
+    };
+
+    let sources = if files.is_empty() { vec![None] } else { files.iter().map(|path| Some(path.as_str())).collect() };
+    let mut status = 0;
+    for path in sources {
+        let input = read(path);
+        let doc = match jacl::read_string(&input) {
+            Ok(doc) => doc,
+            Err(err) => {
+                report_as(json, path, &err);
+                status = 1;
+                continue;
+            },
+        };
+        if let Err(err) = schema.validate(&doc) {
+            report_as(json, path, &err);
+            status = 1;
+        }
+    }
+    status
+}
+
+fn infer(args: impl Iterator<Item = String>) -> i32 {
+    let mut files = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            _ if arg.starts_with("--") => fail(&format!("Unknown option {}", arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    let sources = if files.is_empty() { vec![None] } else { files.iter().map(|path| Some(path.as_str())).collect() };
+    let inputs = sources.iter().map(|path| read(*path)).collect::<Vec<String>>();
+    let mut docs = Vec::new();
+    let mut status = 0;
+    for (path, input) in sources.iter().zip(&inputs) {
+        match jacl::read_string(input) {
+            Ok(doc) => docs.push(doc),
+            Err(err) => {
+                report(*path, &err);
+                status = 1;
+            },
+        }
+    }
+
+    if status == 0 {
+        print!("{}", jacl::infer_schema(&docs));
+    }
+    status
+}
+
+fn explain(mut args: impl Iterator<Item = String>) -> i32 {
+    let code = args.next().unwrap_or_else(|| fail("explain expects an error code"));
+    match jacl::explain(&code) {
+        Some(text) => { println!("{}", text); 0 },
+        None => { eprintln!("'{}' is not a JACL error code", code); 1 },
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let status = match args.next().as_deref() {
+        Some("fmt") => fmt(args),
+        Some("query") => query(args),
+        Some("check") => check(args),
+        Some("validate") => validate(args),
+        Some("infer") => infer(args),
+        Some("explain") => explain(args),
+        Some("-h") | Some("--help") | Some("help") => { println!("{}", USAGE); 0 },
+        Some(cmd) => fail(&format!("Unknown command {}", cmd)),
+        None => fail("No command given"),
+    };
+    process::exit(status);
+}
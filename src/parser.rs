@@ -2,74 +2,147 @@ use indexmap::map::IndexMap;
 
 use crate::Lines;
 use crate::tokeniser::{Token, TokVal};
-use crate::error::Error;
-use crate::types::{Struct, Value, Entries, Props};
+use crate::error::{Error, ErrorCode};
+use crate::types::{Struct, Value, Entries, Props, Meta, Span, ValueSpan, PropSpan};
+use crate::util::did_you_mean;
+
+const MAP_NOTE: &str = "Maps hold only properties. Use an Object to hold entries and properties together.";
+const TABLE_NOTE: &str = "Tables hold only entries. Use an Object to hold entries and properties together.";
+
+/// Point out where an entry was first defined, if it is known
+fn first_defined(err: Error, span: Option<Span>) -> Error {
+    match span {
+        Some(span) => err.with_label(span, "First defined here"),
+        None => err,
+    }
+}
+
+fn is_anon(key: &str) -> bool {
+    key.starts_with('#')
+}
+
+/// Point Keys of anonymous entries at their new names
+fn rename_keys(renamed: &IndexMap<String, String>, props: Option<&mut Props>, meta: &mut Meta) {
+    let rename = |val: &mut Value| {
+        if let Value::Key(key) = val {
+            if let Some(new) = renamed.get(key.as_str()) {
+                *key = new.clone();
+            }
+        }
+    };
+    for val in props.into_iter().flat_map(|props| props.values_mut()) {
+        rename(val);
+    }
+    for def in meta.props.values_mut().flat_map(|prop| prop.history.iter_mut()) {
+        rename(&mut def.value);
+    }
+}
 
 impl Struct {
+    /// The names anonymous entries of `new_entries` will take once added to
+    /// `ex_entries`, so that they follow on from those already there
+    fn anon_renames(ex_entries: &Entries, new_entries: &Entries) -> IndexMap<String, String> {
+        let mut next = ex_entries.len();
+        let mut renamed = IndexMap::new();
+        for key in new_entries.keys() {
+            if is_anon(key) {
+                renamed.insert(key.clone(), format!("#anon{}", next));
+                next += 1;
+            }
+            else if !ex_entries.contains_key(key) {
+                next += 1;
+            }
+        }
+        renamed
+    }
+
     fn entries_extend<'ln, 'src>(parser: &mut Parser<'ln, 'src>,
                             ex_entries: &mut Entries,
-                            new_entries: &Entries) -> Result<(), Error<'src>> {
-        for (new_key, new_entry) in new_entries.iter() {
+                            ex_meta: &mut Meta,
+                            new_entries: &Entries,
+                            new_meta: &Meta,
+                            renamed: &IndexMap<String, String>) -> Result<(), Error> {
+        for (key, new_entry) in new_entries.iter() {
+            let new_key = renamed.get(key).unwrap_or(key);
             if let Some(Some(ex_entry)) = ex_entries.get_mut(new_key) {
                 if let Some(new_entry) = new_entry {
                     ex_entry.extend(parser, new_key, new_entry.clone())?;
                 }
                 else {
-                    return Err(Error::detailed(162, format!("Entry {} redefined with no new data", new_key),
-                               parser.cur_expect()?.clone(), String::from("Remove this redefinition")));
+                    let err = Error::detailed(ErrorCode::EmptyRedefinition, format!("Entry {} redefined with no new data", new_key),
+                                              parser.cur_expect()?.clone(), String::from("Remove this redefinition"));
+                    return Err(first_defined(err, ex_meta.entries.get(new_key).and_then(|spans| spans.first()).copied()));
                 }
             }
             else {
                 ex_entries.insert(new_key.clone(), new_entry.clone());
             }
+            if let Some(spans) = new_meta.entries.get(key) {
+                for span in spans {
+                    ex_meta.define_entry(new_key, *span);
+                }
+            }
         }
         Ok(())
     }
 
     fn extend<'ln, 'src>(&mut self,
                     parser: &mut Parser<'ln, 'src>,
-                    name: &str, new: Struct) -> Result<(), Error<'src>>{
+                    name: &str, new: Struct) -> Result<(), Error>{
+        let first = self.meta().span;
         match self {
             Struct::Object { entries: ex_entries,
-                                  props: ex_props } => {
+                                  props: ex_props,
+                                  meta: ex_meta } => {
                 if let Struct::Object { entries: new_entries,
-                                        props: new_props } = new {
-                    ex_props.extend(new_props);
-                    Struct::entries_extend(parser, ex_entries, &new_entries)
+                                        props: mut new_props,
+                                        meta: mut new_meta } = new {
+                    let renamed = Struct::anon_renames(ex_entries, &new_entries);
+                    rename_keys(&renamed, Some(&mut new_props), &mut new_meta);
+                    for (name, val) in new_props {
+                        let old = ex_props.insert(name.clone(), val);
+                        if let Some(prop_span) = new_meta.props.get(&name) {
+                            ex_meta.define_prop(&name, prop_span.clone(), old);
+                        }
+                    }
+                    Struct::entries_extend(parser, ex_entries, ex_meta, &new_entries, &new_meta, &renamed)
                 }
                 else {
-                     Err(Error::detailed(161, format!("Entry {} already defined as Object", name),
-                         parser.cur_expect()?.clone(), String::from("Make this entry an Object")))
+                     Err(first_defined(Error::detailed(ErrorCode::AlreadyObject, format!("Entry {} already defined as Object", name),
+                         parser.cur_expect()?.clone(), String::from("Make this entry an Object")), first))
                 }
             },
-            Struct::Table { entries: ex_entries } => {
-                if let Struct::Table { entries: new_entries } = new {
-                    Struct::entries_extend(parser, ex_entries, &new_entries)
+            Struct::Table { entries: ex_entries, meta: ex_meta } => {
+                if let Struct::Table { entries: new_entries, meta: new_meta } = new {
+                    let renamed = Struct::anon_renames(ex_entries, &new_entries);
+                    Struct::entries_extend(parser, ex_entries, ex_meta, &new_entries, &new_meta, &renamed)
                 }
                 else {
-                    Err(Error::detailed(160, format!("Entry {} already defined as Table", name),
-                        parser.cur_expect()?.clone(), String::from("Make this entry a Table")))
+                    Err(first_defined(Error::detailed(ErrorCode::AlreadyTable, format!("Entry {} already defined as Table", name),
+                        parser.cur_expect()?.clone(), String::from("Make this entry a Table")), first))
                 }
            },
            Struct::Map {..} => {
-               Err(Error::detailed(159, format!("Entry {} already defined as Map", name),
-                   parser.cur_expect()?.clone(), String::from("Make this entry a Map")))
+               Err(first_defined(Error::detailed(ErrorCode::AlreadyMap, format!("Entry {} already defined as Map", name),
+                   parser.cur_expect()?.clone(), String::from("Make this entry a Map")), first))
            }
         }
     }
 }
 
 enum RValue {
-    Value(Value),
-    Struct(Struct),
+    Value(Value, ValueSpan),
+    Struct(Box<Struct>),
 }
 
 struct Parser<'ln, 'src: 'ln> {
     input: &'src str,
+    #[allow(dead_code)]
     lines: &'ln Lines,
     tokens: Vec<Token<'src>>,
 
     ptr: usize,
+    errors: Vec<Error>,
 }
 
 impl<'ln, 'src> Parser<'ln, 'src> {
@@ -79,6 +152,7 @@ impl<'ln, 'src> Parser<'ln, 'src> {
            lines,
            tokens,
            ptr: 0,
+           errors: Vec::new(),
         }
     }
 
@@ -86,37 +160,29 @@ impl<'ln, 'src> Parser<'ln, 'src> {
         self.tokens.get(self.ptr)
     }
 
-    fn cur_expect(&self) -> Result<Token<'src>, Error<'src>> {
+    fn cur_expect(&self) -> Result<Token<'src>, Error> {
         match self.tokens.get(self.ptr) {
             Some(tok) => {
                 Ok(tok.clone())
             },
             None => {
-                Err(Error::basic(154, String::from("Unexpected End-of-file")))
+                Err(Error::basic(ErrorCode::UnexpectedEnd, String::from("Unexpected End-of-file")))
             },
         }
     }
 
-    fn peek(&self, n: usize) -> Option<&Token<'src>> {
-        self.tokens.get(self.ptr + n)
-    }
-
-    fn peek_expect(&self, n: usize) -> Result<&Token<'src>, Error<'src>> {
+    fn peek_expect(&self, n: usize) -> Result<&Token<'src>, Error> {
         match self.tokens.get(self.ptr + n) {
             Some(tok) => {
                 Ok(tok)
             },
             None => {
-                Err(Error::basic(155, String::from("Unexpected End-of-file")))
+                Err(Error::basic(ErrorCode::UnexpectedEndLookahead, String::from("Unexpected End-of-file")))
             },
         }
     }
 
-    fn nxt(&self) -> Option<&Token<'src>> {
-        self.peek(1)
-    }
-
-    fn nxt_expect(&self) -> Result<&Token<'src>, Error<'src>> {
+    fn nxt_expect(&self) -> Result<&Token<'src>, Error> {
         self.peek_expect(1)
     }
 
@@ -125,38 +191,110 @@ impl<'ln, 'src> Parser<'ln, 'src> {
         self.cur()
     }
 
-    fn advance_expect(&mut self, n: usize) -> Result<Token<'src>, Error<'src>> {
-        self.ptr += n;
-        self.cur_expect()
-    }
-
     fn step(&mut self) -> Option<&Token<'src>> {
         self.advance(1)
     }
 
-    fn step_expect(&mut self) -> Result<Token<'src>, Error<'src>> {
-        self.advance_expect(1)
-    }
-
-    fn expect(&mut self, gate: fn(tv: &TokVal) -> bool, exp: &str) -> Result<Token<'src>, Error<'src>> {
+    fn expect(&mut self, gate: fn(tv: &TokVal) -> bool, exp: &str) -> Result<Token<'src>, Error> {
         let ret = match self.cur() {
             Some(tok) => {
                 if gate(&tok.val) {
                     Ok(tok.clone())
                 }
                 else {
-                    Err(Error::detailed(150, format!("Expected {}", exp),
+                    Err(Error::detailed(ErrorCode::ExpectedToken, format!("Expected {}", exp),
                                         tok.clone(), format!("Found {:?}", tok.val)))
                 }
             },
             None => {
-                Err(Error::basic(151, format!("Expected {} but found End-of-File", exp)))
+                let msg = format!("Expected {} but found End-of-File", exp);
+                Err(match self.end_of_input() {
+                    Some(end) => Error::spanned(ErrorCode::ExpectedTokenAtEnd, msg, end, format!("Expected {} here", exp)),
+                    None => Error::basic(ErrorCode::ExpectedTokenAtEnd, msg),
+                })
             }
         };
         self.step();
         ret
     }
     
+    /// Like `expect`, but for the bracket closing the structure opened by
+    /// `open`. Failure is recorded rather than returned so the enclosing
+    /// structure survives. Returns the Span of the last token belonging to
+    /// the structure.
+    fn expect_close(&mut self, open: &Token<'src>, gate: fn(tv: &TokVal) -> bool, exp: &str) -> Span {
+        let last = self.tokens[..usize::min(self.ptr, self.tokens.len())]
+                       .last()
+                       .map(|tok| tok.span());
+        if self.cur().is_none() {
+            self.errors.push(self.unclosed(open, exp));
+            return last.expect("Structure closed before it was opened");
+        }
+        match self.expect(gate, exp) {
+            Ok(tok) => tok.span(),
+            Err(err) => {
+                self.errors.push(err);
+                last.expect("Structure closed before it was opened")
+            }
+        }
+    }
+
+    /// The empty Span just after the last token of the input, ignoring
+    /// line breaks
+    fn end_of_input(&self) -> Option<Span> {
+        self.tokens.iter()
+            .rev()
+            .find(|tok| !matches!(tok.val, TokVal::Break))
+            .map(|tok| {
+                let span = tok.span();
+                Span { start: span.end, end: span.end, line: span.line, col: span.col + tok.len }
+            })
+    }
+
+    /// The input ended before the structure opened by `open` was closed.
+    /// The error points where the closing bracket belongs.
+    fn unclosed(&self, open: &Token<'src>, exp: &str) -> Error {
+        let msg = format!("Expected {} but found End-of-File", exp);
+        let end = match self.end_of_input() {
+            Some(end) => end,
+            None => return Error::basic(ErrorCode::ExpectedTokenAtEnd, msg),
+        };
+        let opener = open.span();
+        let closer = exp.trim_matches('\'');
+        Error::spanned(ErrorCode::ExpectedTokenAtEnd, msg, end, format!("Expected {} here", exp))
+            .with_label(opener, format!("This '{}' is never closed", &self.input[opener.start..opener.end]))
+            .with_suggestion(format!("Add '{}' at the end of the input", closer), end, closer)
+    }
+
+    /// Skip ahead after a syntax error, stopping after the next Break or
+    /// before a closing bracket belonging to the structure we are in.
+    /// `start` is the position of the item in which the error occurred.
+    fn synchronise(&mut self, start: usize) {
+        if self.ptr == start {
+            self.step();
+        }
+        let mut depth: usize = 0;
+        while let Some(tok) = self.cur() {
+            match tok.val {
+                TokVal::Break if depth == 0 => {
+                    self.step();
+                    return;
+                },
+                TokVal::LBrace | TokVal::LBrack | TokVal::LBracePct => {
+                    depth += 1;
+                },
+                TokVal::RBrace | TokVal::RBrack | TokVal::RBracePct => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                },
+                _ => {},
+            }
+            self.step();
+        }
+    }
+
     fn allow_break(&mut self) {
         loop {
             match self.cur() {
@@ -176,11 +314,12 @@ impl<'ln, 'src> Parser<'ln, 'src> {
 
 /* Parse Bindings */
 
-fn parse_val<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<Value, Error<'src>> {
-    match parser.cur_expect()?.val {
+fn parse_val<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(Value, ValueSpan), Error> {
+    let start = parser.cur_expect()?;
+    match start.val {
         TokVal::Name(name) => {
             parser.step();
-            Ok(Value::Key(name.to_string()))
+            Ok((Value::Key(name.to_string()), ValueSpan::new(start.span())))
         }
         TokVal::Dollar => {
             parser.step();
@@ -188,59 +327,77 @@ fn parse_val<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> R
             let tok = parser.expect(|tv| matches!(tv, TokVal::Name(..)), "name")?;
             if let TokVal::Name(name) = tok.val {
                 match strct {
-                    Struct::Object { entries: _, props } |
-                    Struct::Map { props } => {
+                    Struct::Object { props, meta, .. } |
+                    Struct::Map { props, meta } => {
                         match props.get(name) {
-                            Some(val) => Ok(val.clone()),
-                            None => Err(
-                                Error::detailed(165, String::from("No such var"),
+                            Some(val) => {
+                                // The value is copied, so it is located at the reference
+                                let mut vspan = ValueSpan::new(start.span().to(&tok.span()));
+                                if let Some(prop_span) = meta.props.get(name) {
+                                    vspan.elems = prop_span.value.elems.clone();
+                                }
+                                Ok((val.clone(), vspan))
+                            },
+                            None => {
+                                let err = Error::detailed(ErrorCode::NoSuchVar, String::from("No such var"),
                                         tok.clone(), String::from("At this point no property exists with this name"))
-                                                
-                            ),
+                                    .with_note("A var must be bound before it can be referenced");
+                                Err(match did_you_mean(name, props.keys().map(String::as_str)) {
+                                    Some(similar) => err.with_suggestion(format!("Did you mean ${}?", similar), tok.span(), similar),
+                                    None => err,
+                                })
+                            },
                         }
                     },
                     Struct::Table { .. } => {
-                        Err(Error::basic(1, String::from("Internal Error: tried to parse val within a Table")))
+                        Err(Error::basic(ErrorCode::Internal, String::from("Internal Error: tried to parse val within a Table")))
                     },
                 }
             }
             else { Err(
-                Error::basic(1, String::from("Internal Error: Reached the unreachable!"))
+                Error::basic(ErrorCode::Internal, String::from("Internal Error: Reached the unreachable!"))
             )}
         },
         TokVal::String(string) => {
             parser.step();
-            Ok(Value::String(string.to_string()))
+            Ok((Value::String(string.to_string()), ValueSpan::new(start.span())))
         },
         TokVal::Integer(integer) => {
             parser.step();
-            Ok(Value::Integer(integer))
+            Ok((Value::Integer(integer), ValueSpan::new(start.span())))
         },
         TokVal::Float(float) => {
             parser.step();
-            Ok(Value::Float(float))
+            Ok((Value::Float(float), ValueSpan::new(start.span())))
         },
         TokVal::Boolean(boolean) => {
             parser.step();
-            Ok(Value::Boolean(boolean))
+            Ok((Value::Boolean(boolean), ValueSpan::new(start.span())))
         }
         TokVal::LParen => {
             parser.step();
             let mut tuple: Vec<Value> = Vec::new();
+            let mut elems: Vec<ValueSpan> = Vec::new();
             loop {
-                tuple.push(parse_val(parser, strct)?);
+                // Tuples may be split over several lines
+                parser.allow_break();
+                let (val, vspan) = parse_val(parser, strct)?;
+                tuple.push(val);
+                elems.push(vspan);
+                parser.allow_break();
                 let tok = parser.cur_expect()?;
                 match tok.val {
                     TokVal::RParen => {
                         parser.step();
-                        break;
+                        let span = start.span().to(&tok.span());
+                        return Ok((Value::Tuple(tuple), ValueSpan { span, elems }));
                     },
                     TokVal::Comma => {
                         parser.step();
                     },
                     _ => {
                         return Err(
-                            Error::detailed(164,
+                            Error::detailed(ErrorCode::InvalidTuple,
                                 String::from("Invalid syntax inside tuple"),
                                 tok.clone(),
                                 String::from("Expected ',' or ')'"))
@@ -248,87 +405,96 @@ fn parse_val<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> R
                     }
                 }
             }
-            Ok(Value::Tuple(tuple))
         },
         _ => {
             Err(
-                Error::detailed(158,
+                Error::detailed(ErrorCode::ExpectedValue,
                                 String::from("Expected Value"),
-                                parser.cur_expect()?.clone(),
+                                start.clone(),
                                 String::from("Make this a value"))
             )
         }
     }
 }
 
-fn parse_rval<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<RValue, Error<'src>> {
+fn parse_rval<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<RValue, Error> {
     match parser.cur_expect()?.val {
         TokVal::LBrace | TokVal::LBrack | TokVal::LBracePct => {
-            Ok(RValue::Struct(parse_struct(parser)?))
+            Ok(RValue::Struct(Box::new(parse_struct(parser)?)))
         }
         _ => {
-            Ok(RValue::Value(parse_val(parser, strct)?))
+            let (val, vspan) = parse_val(parser, strct)?;
+            Ok(RValue::Value(val, vspan))
         }
     }
 }
 
-fn parse_rhs<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct, names: Vec<String>) -> Result<(), Error<'src>> {
+fn parse_rhs<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct, names: Vec<(String, Span)>) -> Result<(), Error> {
     let eq = parser.expect(|tv| matches!(tv, TokVal::Equals), "'='")?;
     let rval_start = parser.cur_expect()?;
     match parse_rval(parser, strct)? {
-        RValue::Value(val) => {
+        RValue::Value(val, vspan) => {
             match strct {
-                Struct::Object { entries: _ , props } |
-                Struct::Map { props } => {
-                    for name in names {
-                        props.insert(name.to_string(), val.clone());
+                Struct::Object { props, meta, .. } |
+                Struct::Map { props, meta } => {
+                    for (name, name_span) in names {
+                        let old = props.insert(name.clone(), val.clone());
+                        meta.define_prop(&name, PropSpan::new(name_span, vspan.clone()), old);
                     }
                     Ok(())
                 },
-                Struct::Table { entries } => {
-                     Err(Error::detailed(157, String::from("Tables cannot contain Bindings"),
-                             eq.clone(), String::from("Remove this entry")))
+                Struct::Table { .. } => {
+                     Err(Error::detailed(ErrorCode::BindingInTable, String::from("Tables cannot contain Bindings"),
+                             eq.clone(), String::from("Remove this entry"))
+                         .with_note(TABLE_NOTE))
                 }
             }
         },
         RValue::Struct(st) => {
             match strct {
-                Struct::Object { entries, props } => {
+                Struct::Object { entries, props, meta } => {
                     let anon_key = format!("#anon{}", entries.len());
-                    for name in names {
-                        props.insert(name.to_string(), Value::Key(anon_key.clone()));
+                    let st_span = st.meta().span.unwrap_or_else(|| rval_start.span());
+                    for (name, name_span) in names {
+                        let old = props.insert(name.clone(), Value::Key(anon_key.clone()));
+                        meta.define_prop(&name, PropSpan::new(name_span, ValueSpan::new(st_span)), old);
                     }
-                    entries.insert(anon_key, Some(st));
+                    meta.define_entry(&anon_key, st_span);
+                    entries.insert(anon_key, Some(*st));
                     Ok(())
                 },
-                Struct::Map { props } => {
-                    Err(Error::detailed(156, String::from("Maps cannot contain Entries"),
-                             rval_start.clone(), String::from("Remove this entry")))
+                Struct::Map { .. } => {
+                    Err(Error::detailed(ErrorCode::EntryInMap, String::from("Maps cannot contain Entries"),
+                             rval_start.clone(), String::from("Remove this entry"))
+                        .with_note(MAP_NOTE))
                 },
-                Struct::Table { entries } => {
-                     Err(Error::detailed(157, String::from("Tables cannot contain Bindings"),
-                             eq.clone(), String::from("Remove this entry")))
+                Struct::Table { .. } => {
+                     Err(Error::detailed(ErrorCode::BindingInTable, String::from("Tables cannot contain Bindings"),
+                             eq.clone(), String::from("Remove this entry"))
+                         .with_note(TABLE_NOTE))
                 }
             }
         },
     }
 }
 
-fn parse_single_binding<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
-    if let TokVal::Name(name) = parser.cur_expect()?.val {
+fn parse_single_binding<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error> {
+    let tok = parser.cur_expect()?;
+    if let TokVal::Name(name) = tok.val {
         parser.step();
-        parse_rhs(parser, strct, vec![name.to_string()])
+        parse_rhs(parser, strct, vec![(name.to_string(), tok.span())])
     }
     else {
-        Err(Error::basic(1, String::from("Internal Parser Error: TokVal was not Name")))
+        Err(Error::basic(ErrorCode::Internal, String::from("Internal Parser Error: TokVal was not Name")))
     }
 }
 
-fn parse_multiple_binding<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
+fn parse_multiple_binding<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error> {
     let mut names = Vec::new();
     loop {
-        if let TokVal::Name(name) = parser.expect(|tv| matches!(tv, TokVal::Name{..}), "name")?.val {
-            names.push(name.to_string());
+        let name_tok = parser.expect(|tv| matches!(tv, TokVal::Name{..}), "name")?;
+        if let TokVal::Name(name) = name_tok.val {
+            names.push((name.to_string(), name_tok.span()));
             let tok = parser.cur_expect()?;
             match tok.val {
                 TokVal::Equals => {
@@ -339,13 +505,13 @@ fn parse_multiple_binding<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut
                     continue;
                 },
                 _ => {
-                    return Err(Error::detailed(163, String::from("Expected '=' or ','"),
+                    return Err(Error::detailed(ErrorCode::ExpectedEqualsOrComma, String::from("Expected '=' or ','"),
                             tok.clone(), String::from("Could not parse this token")));
                 }
             }
         }
         else { 
-            return Err(Error::basic(1, String::from("Internal Error: Reached the unreachable!")));
+            return Err(Error::basic(ErrorCode::Internal, String::from("Internal Error: Reached the unreachable!")));
         }
     }
     parse_rhs(parser, strct, names)
@@ -353,53 +519,48 @@ fn parse_multiple_binding<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut
 
 /* Parse Entries */
 
-fn parse_single_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
+fn parse_single_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error> {
     match strct {
-        Struct::Object { entries, props: _ } |
-        Struct::Table { entries } => {
-            if let TokVal::Name(name) = parser.cur_expect()?.val {
+        Struct::Object { entries, meta, .. } |
+        Struct::Table { entries, meta } => {
+            let tok = parser.cur_expect()?;
+            if let TokVal::Name(name) = tok.val {
                 parser.step();
                 let strct = parse_struct(parser)?;
-                if let Some(extant) = entries.get_mut(name) {
-                    match extant {
-                        Some(extant) => {
-                            extant.extend(parser, name, strct)
-                        },
-                        None => {
-                            entries.insert(name.to_string(), Some(strct));
-                            Ok(())
-                        }
-                    }
+                if let Some(Some(extant)) = entries.get_mut(name) {
+                    extant.extend(parser, name, strct)?;
                 }
                 else {
                     entries.insert(name.to_string(), Some(strct));
-                    Ok(())
                 }
+                meta.define_entry(name, tok.span());
+                Ok(())
             }
             else {
-                Err(Error::basic(1, String::from("Internal Parser Error: TokVal was not Name")))
+                Err(Error::basic(ErrorCode::Internal, String::from("Internal Parser Error: TokVal was not Name")))
             }
         },
-        Struct::Map { props: _ } => {
-            Err(Error::detailed(156, String::from("Maps cannot contain Entries"),
-                                parser.cur_expect()?.clone(), String::from("Remove this entry")))
+        Struct::Map { .. } => {
+            Err(Error::detailed(ErrorCode::EntryInMap, String::from("Maps cannot contain Entries"),
+                                parser.cur_expect()?.clone(), String::from("Remove this entry"))
+                .with_note(MAP_NOTE))
         }
     }
 }
 
-fn parse_compound_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
+fn parse_compound_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error> {
     match strct {
-        Struct::Object { entries, props: _ } |
-        Struct::Table { entries } => {
+        Struct::Object { entries, meta, .. } |
+        Struct::Table { entries, meta } => {
             let mut names = Vec::new();
             loop {
-                if let TokVal::Name(name) = parser.expect(
-                        |tv| matches!(tv, TokVal::Name(..)), "name")?.val {
-                    names.push(name); 
+                let tok = parser.expect(|tv| matches!(tv, TokVal::Name(..)), "name")?;
+                if let TokVal::Name(name) = tok.val {
+                    names.push((name, tok.span()));
                 }
                 else {
                     return Err(
-                        Error::basic(1, String::from("Internal Error: Reached the unreachable!"))
+                        Error::basic(ErrorCode::Internal, String::from("Internal Error: Reached the unreachable!"))
                     );
                 }
                 if !matches!(parser.cur_expect()?.val, TokVal::Plus) { break; }
@@ -407,85 +568,123 @@ fn parse_compound_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut S
             }
 
             let strct = parse_struct(parser)?;
-            for name in names {
-                if let Some(extant) = entries.get_mut(name) {
-                    match extant {
-                        Some(extant) => {
-                            extant.extend(parser, name, strct.clone())?;
-                        },
-                        None => {
-                            entries.insert(name.to_string(), Some(strct.clone()));
-                        }
-                    }
+            for (name, name_span) in names {
+                if let Some(Some(extant)) = entries.get_mut(name) {
+                    extant.extend(parser, name, strct.clone())?;
                 }
                 else {
                     entries.insert(name.to_string(), Some(strct.clone()));
                 }
+                meta.define_entry(name, name_span);
             }
             Ok(())
         },
-        Struct::Map { props: _ } => {
-            Err(Error::detailed(156, String::from("Maps cannot contain Entries"),
-                                parser.cur_expect()?.clone(), String::from("Remove this entry")))
+        Struct::Map { .. } => {
+            Err(Error::detailed(ErrorCode::EntryInMap, String::from("Maps cannot contain Entries"),
+                                parser.cur_expect()?.clone(), String::from("Remove this entry"))
+                .with_note(MAP_NOTE))
         }
     }
 }
 
-fn parse_wild_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
-    Err(Error::basic(1, String::from("Internal Error: Unimplemented")))
+fn parse_wild_entry<'ln, 'src>(_parser: &mut Parser<'ln, 'src>, _strct: &mut Struct) -> Result<(), Error> {
+    Err(Error::basic(ErrorCode::Internal, String::from("Internal Error: Unimplemented")))
 }
 
-fn parse_prop_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
-    Err(Error::basic(1, String::from("Internal Error: Unimplemented")))
+fn parse_prop_entry<'ln, 'src>(_parser: &mut Parser<'ln, 'src>, _strct: &mut Struct) -> Result<(), Error> {
+    Err(Error::basic(ErrorCode::Internal, String::from("Internal Error: Unimplemented")))
 }
 
-fn parse_empty_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
+fn parse_empty_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error> {
     match strct {
-        Struct::Object { entries, props: _ } |
-        Struct::Table { entries } => {
+        Struct::Object { entries, meta, .. } |
+        Struct::Table { entries, meta } => {
             let tok = parser.cur_expect()?;
             if let TokVal::Name(name) = tok.val {
                 parser.step();
                 match entries.get(name) {
                     Some(_) => {
-                        Err(Error::detailed(162, format!("Entry {} redefined with no new data", name),
-                            tok.clone(), String::from("Remove this redefinition")))
+                        let err = Error::detailed(ErrorCode::EmptyRedefinition, format!("Entry {} redefined with no new data", name),
+                                                  tok.clone(), String::from("Remove this redefinition"))
+                                      .with_suggestion("Remove the redefinition", tok.span(), "");
+                        Err(first_defined(err, meta.entries.get(name).and_then(|spans| spans.first()).copied()))
                     },
                     None => {
                         entries.insert(name.to_string(), None);
+                        meta.define_entry(name, tok.span());
                         Ok(())
                     }
                 }
             }
             else {
-                Err(Error::basic(1, String::from("Internal Parser Error: TokVal was not Name")))
+                Err(Error::basic(ErrorCode::Internal, String::from("Internal Parser Error: TokVal was not Name")))
             }
         },
-        Struct::Map { props: _ } => {
-            Err(Error::detailed(156, String::from("Maps cannot contain Entries"),
-                                parser.cur_expect()?.clone(), String::from("Remove this entry")))
+        Struct::Map { .. } => {
+            Err(Error::detailed(ErrorCode::EntryInMap, String::from("Maps cannot contain Entries"),
+                                parser.cur_expect()?.clone(), String::from("Remove this entry"))
+                .with_note(MAP_NOTE))
         }
     }
 }
 
-fn parse_anon_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
+fn parse_anon_entry<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error> {
     match strct {
-        Struct::Object { entries, props: _ } |
-        Struct::Table { entries } => {
+        Struct::Object { entries, meta, .. } |
+        Struct::Table { entries, meta } => {
+            let start = parser.cur_expect()?;
             let strct = parse_struct(parser)?;
-            entries.insert(format!("#anon{}", entries.len()), Some(strct));
+            let anon_key = format!("#anon{}", entries.len());
+            meta.define_entry(&anon_key, strct.meta().span.unwrap_or_else(|| start.span()));
+            entries.insert(anon_key, Some(strct));
             Ok(())
         },
-        Struct::Map { props: _ } => {
-            Err(Error::detailed(156, String::from("Maps cannot contain Entries"),
-                                parser.cur_expect()?.clone(), String::from("Remove this entry")))
+        Struct::Map { .. } => {
+            Err(Error::detailed(ErrorCode::EntryInMap, String::from("Maps cannot contain Entries"),
+                                parser.cur_expect()?.clone(), String::from("Remove this entry"))
+                .with_note(MAP_NOTE))
         }
     }
 }
 
 /* Parse Structures */
 
-fn parse_inner<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error<'src>> {
+fn parse_item<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) -> Result<(), Error> {
+    match parser.cur_expect()?.val {
+        TokVal::Name(_) => {
+            let nxt = parser.nxt_expect()?;
+            match nxt.val {
+                TokVal::Comma => { // multi binding
+                    parse_multiple_binding(parser, strct)
+                },
+                TokVal::Equals => { // single binding
+                    parse_single_binding(parser, strct)
+                },
+                TokVal::Plus => { // compound selector
+                    parse_compound_entry(parser, strct)
+                },
+                TokVal::Break | TokVal::RBrace |
+                TokVal::RBrack | TokVal::RBracePct => { // atomic obj
+                    parse_empty_entry(parser, strct)
+                },
+                TokVal::Star => {
+                    parse_wild_entry(parser, strct)
+                },
+                TokVal::Dollar => {
+                    parse_prop_entry(parser, strct)
+                },
+                _ => { // assume we have a simple selector
+                    parse_single_entry(parser, strct)
+                }
+            }
+        },
+        _ => {
+            parse_anon_entry(parser, strct)
+        }
+    }
+}
+
+fn parse_inner<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) {
     loop {
         parser.allow_break();
         let cur_tok = parser.cur();
@@ -494,35 +693,12 @@ fn parse_inner<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) ->
                 TokVal::RBrace | TokVal::RBrack | TokVal::RBracePct => {
                     break;
                 },
-                TokVal::Name(name) => {
-                    let nxt = parser.nxt_expect()?;
-                    match nxt.val {
-                        TokVal::Comma => { // multi binding
-                            parse_multiple_binding(parser, strct)?;
-                        },
-                        TokVal::Equals => { // single binding
-                            parse_single_binding(parser, strct)?;
-                        },
-                        TokVal::Plus => { // compound selector
-                            parse_compound_entry(parser, strct)?;
-                        },
-                        TokVal::Break | TokVal::RBrace |
-                        TokVal::RBrack | TokVal::RBracePct => { // atomic obj
-                            parse_empty_entry(parser, strct)?;
-                        },
-                        TokVal::Star => {
-                            parse_wild_entry(parser, strct)?;
-                        },
-                        TokVal::Dollar => {
-                            parse_prop_entry(parser, strct)?;
-                        },
-                        _ => { // assume we have a simple selector
-                            parse_single_entry(parser, strct)?;
-                        }
-                    }
-                },
                 _ => {
-                    parse_anon_entry(parser, strct)?;
+                    let start = parser.ptr;
+                    if let Err(err) = parse_item(parser, strct) {
+                        parser.errors.push(err);
+                        parser.synchronise(start);
+                    }
                 }
             }
         }
@@ -530,42 +706,47 @@ fn parse_inner<'ln, 'src>(parser: &mut Parser<'ln, 'src>, strct: &mut Struct) ->
             break;
         }
     }
-    Ok(())
 }
 
 
-fn parse_obj_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error<'src>> {
-    parser.expect(|tv| matches!(tv, TokVal::LBrace), "'{'")?;
+fn parse_obj_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error> {
+    let open = parser.expect(|tv| matches!(tv, TokVal::LBrace), "'{'")?;
     let mut obj = Struct::Object {
         entries: IndexMap::new(),
         props: IndexMap::new(),
+        meta: Meta::default(),
     };
-    parse_inner(parser, &mut obj)?;
-    parser.expect(|tv| matches!(tv, TokVal::RBrace), "'}'")?;
+    parse_inner(parser, &mut obj);
+    let close = parser.expect_close(&open, |tv| matches!(tv, TokVal::RBrace), "'}'");
+    obj.meta_mut().span = Some(open.span().to(&close));
     Ok(obj)
 }
 
-fn parse_tbl_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error<'src>> {
-    parser.expect(|tv| matches!(tv, TokVal::LBrack), "'['")?;
+fn parse_tbl_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error> {
+    let open = parser.expect(|tv| matches!(tv, TokVal::LBrack), "'['")?;
     let mut tbl = Struct::Table {
         entries: IndexMap::new(),
+        meta: Meta::default(),
     };
-    parse_inner(parser, &mut tbl)?;
-    parser.expect(|tv| matches!(tv, TokVal::RBrack), "']'")?;
+    parse_inner(parser, &mut tbl);
+    let close = parser.expect_close(&open, |tv| matches!(tv, TokVal::RBrack), "']'");
+    tbl.meta_mut().span = Some(open.span().to(&close));
     Ok(tbl)
 }
 
-fn parse_map_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error<'src>> {
-    parser.expect(|tv| matches!(tv, TokVal::LBracePct), "'{%'")?;
+fn parse_map_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error> {
+    let open = parser.expect(|tv| matches!(tv, TokVal::LBracePct), "'{%'")?;
     let mut map = Struct::Map {
         props: IndexMap::new(),
+        meta: Meta::default(),
     };
-    parse_inner(parser, &mut map)?;
-    parser.expect(|tv| matches!(tv, TokVal::RBracePct), "'%}'")?;
+    parse_inner(parser, &mut map);
+    let close = parser.expect_close(&open, |tv| matches!(tv, TokVal::RBracePct), "'%}'");
+    map.meta_mut().span = Some(open.span().to(&close));
     Ok(map)
 }
 
-fn parse_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error<'src>> {
+fn parse_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Error> {
     let tok = parser.cur_expect()?;
     match tok.val {
         TokVal::LBrace => {
@@ -578,22 +759,37 @@ fn parse_struct<'ln, 'src>(parser: &mut Parser<'ln, 'src>) -> Result<Struct, Err
             parse_map_struct(parser)
         },
         _ => {
-            Err(Error::detailed(153, String::from("Expected Struct"),
+            Err(Error::detailed(ErrorCode::ExpectedStruct, String::from("Expected Struct"),
                                 tok.clone(), format!("Found {:?}", tok.val)))
         },
     }
 }
 
-pub fn parse<'ln, 'src>(input: &'src str,
-                   lines: &'ln Lines,
-                   tokens: Vec<Token<'src>>) -> Result<Struct, Error<'src>> {
+/// Parse a token stream into the root Object.
+///
+/// The parser recovers from syntax errors, so the returned Struct holds
+/// everything that could be understood, alongside every error found.
+pub fn parse<'src>(input: &'src str,
+                   lines: &Lines,
+                   tokens: Vec<Token<'src>>) -> (Struct, Vec<Error>) {
     let mut parser = Parser::new(input, lines, tokens);
     let mut root = Struct::Object {
         entries: IndexMap::new(),
         props: IndexMap::new(),
+        meta: Meta::default(),
     };
-    parse_inner(&mut parser, &mut root)?;
-    Ok(root)
+    loop {
+        parse_inner(&mut parser, &mut root);
+        match parser.cur() {
+            Some(tok) => {
+                let err = Error::detailed(ErrorCode::UnmatchedBracket, String::from("Unmatched closing bracket"),
+                                          tok.clone(), String::from("Remove this bracket"))
+                                .with_suggestion("Remove the bracket", tok.span(), "");
+                parser.errors.push(err);
+                parser.step();
+            },
+            None => break,
+        }
+    }
+    (root, parser.errors)
 }
-
-
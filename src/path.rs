@@ -0,0 +1,338 @@
+use std::fmt;
+
+use crate::Jacl;
+use crate::api::{view, JaclStruct};
+use crate::types::{Struct, Value};
+use crate::util::did_you_mean;
+use crate::writer::{write_struct, write_value, WriteError};
+
+/// One step of a path such as `servers.freenode.port` or `filters[0].user`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Key(key) => write!(f, "{}", key),
+            Segment::Index(idx) => write!(f, "[{}]", idx),
+        }
+    }
+}
+
+/// Render `segments` as a path, as accepted by `parse_path`
+pub fn format_path(segments: &[Segment]) -> String {
+    let mut out = String::new();
+    for seg in segments {
+        if let Segment::Key(_) = seg {
+            if !out.is_empty() {
+                out.push('.');
+            }
+        }
+        out.push_str(&seg.to_string());
+    }
+    out
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Split a path into its segments. Keys are separated by `.`,
+/// and indices are written in square brackets after a key.
+pub fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut chars = path.char_indices().peekable();
+    let mut expect_key = true;
+
+    while let Some(&(offset, c)) = chars.peek() {
+        if c == '[' {
+            chars.next();
+            let mut digits = String::new();
+            while let Some(&(_, d)) = chars.peek() {
+                if !d.is_ascii_digit() {
+                    break;
+                }
+                digits.push(d);
+                chars.next();
+            }
+            match chars.next() {
+                Some((_, ']')) if !digits.is_empty() => {
+                    let idx = digits.parse::<usize>()
+                                    .map_err(|_| format!("Index {} is too large", digits))?;
+                    segments.push(Segment::Index(idx));
+                },
+                _ => return Err(format!("Malformed index at byte {}", offset)),
+            }
+            expect_key = false;
+        }
+        else if c == '.' {
+            if expect_key {
+                return Err(format!("Expected a key at byte {}", offset));
+            }
+            chars.next();
+            expect_key = true;
+        }
+        else if is_name_char(c) {
+            if !expect_key {
+                return Err(format!("Expected '.' or '[' at byte {}", offset));
+            }
+            let mut key = String::new();
+            while let Some(&(_, k)) = chars.peek() {
+                if !is_name_char(k) {
+                    break;
+                }
+                key.push(k);
+                chars.next();
+            }
+            segments.push(Segment::Key(key));
+            expect_key = false;
+        }
+        else {
+            return Err(format!("Unexpected character '{}' at byte {}", c, offset));
+        }
+    }
+
+    if expect_key && !segments.is_empty() {
+        return Err(String::from("Path ends with '.'"));
+    }
+    Ok(segments)
+}
+
+/* Path Lookup */
+
+/// Why a path could not be followed
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathErrorKind {
+    /// The path itself could not be parsed
+    Syntax(String),
+    /// There is no entry or property with this name, but there may be
+    /// one with a similar name
+    NotFound { similar: Option<String> },
+    /// A Key which names no entry, though there is one with a similar name
+    UnresolvedKey { key: String, similar: String },
+    /// The index is past the end of the Table or Tuple
+    OutOfRange { len: usize },
+    /// Names cannot be looked up within this
+    NoNames(&'static str),
+    /// Indices cannot be looked up within this
+    NoIndices(&'static str),
+}
+
+/// A path which could not be followed, and the segment where it failed
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathError {
+    /// The path up to and including the failing segment
+    pub at: String,
+    /// The failing segment, or None if the path could not be parsed
+    pub segment: Option<Segment>,
+    pub kind: PathErrorKind,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.kind, &self.segment) {
+            (PathErrorKind::Syntax(msg), _) => write!(f, "Invalid path '{}': {}", self.at, msg),
+            (PathErrorKind::NotFound { similar }, Some(seg)) => {
+                write!(f, "{}: no entry or property named '{}'", self.at, seg)?;
+                match similar {
+                    Some(similar) => write!(f, "; did you mean '{}'?", similar),
+                    None => Ok(()),
+                }
+            },
+            (PathErrorKind::UnresolvedKey { key, similar }, Some(_)) => {
+                write!(f, "{}: the Key '{}' names no entry; did you mean '{}'?", self.at, key, similar)
+            },
+            (PathErrorKind::OutOfRange { len }, Some(seg)) => write!(f, "{}: {} is out of range for length {}", self.at, seg, len),
+            (PathErrorKind::NoNames(what), Some(seg)) => write!(f, "{}: cannot look up '{}' in {}", self.at, seg, what),
+            (PathErrorKind::NoIndices(what), Some(seg)) => write!(f, "{}: cannot index {} with {}", self.at, what, seg),
+            (_, None) => write!(f, "{}: lookup failed", self.at),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Whatever a path leads to
+#[derive(Debug)]
+pub enum Item<'s> {
+    Struct(JaclStruct<'s>),
+    Value(&'s Value),
+    /// An entry without a structure, like `atom`
+    Empty,
+}
+
+impl<'s> Item<'s> {
+    pub fn as_struct(&self) -> Option<&JaclStruct<'s>> {
+        match self {
+            Item::Struct(strct) => Some(strct),
+            _ => None,
+        }
+    }
+
+    pub fn as_value(&self) -> Option<&'s Value> {
+        match self {
+            Item::Value(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Write this as JACL text on a single line. Empty entries have no text.
+    pub fn to_jacl_string(&self) -> Result<String, WriteError> {
+        match self {
+            Item::Struct(strct) => write_struct(strct.data(), 0, false),
+            Item::Value(val) => write_value(val),
+            Item::Empty => Ok(String::new()),
+        }
+    }
+}
+
+/// What a path has reached so far, while it is being followed
+#[derive(Clone, Copy)]
+pub(crate) enum Found<'s> {
+    Struct(&'s Struct),
+    Value(&'s Value, &'s Struct),
+    Empty,
+}
+
+impl<'s> Found<'s> {
+    pub(crate) fn into_item(self, jacl: &'s Jacl) -> Item<'s> {
+        match self {
+            Found::Struct(strct) => Item::Struct(view(strct, jacl)),
+            Found::Value(val, _) => Item::Value(val),
+            Found::Empty => Item::Empty,
+        }
+    }
+}
+
+fn describe(found: Found<'_>) -> &'static str {
+    match found {
+        Found::Struct(Struct::Object { .. }) => "an Object",
+        Found::Struct(Struct::Table { .. }) => "a Table",
+        Found::Struct(Struct::Map { .. }) => "a Map",
+        Found::Value(Value::Tuple(_), _) => "a Tuple",
+        Found::Value(Value::Key(_), _) => "a Key",
+        Found::Value(Value::String(_), _) => "a String",
+        Found::Value(Value::Integer(_), _) => "an Integer",
+        Found::Value(Value::Float(_), _) => "a Float",
+        Found::Value(Value::Boolean(_), _) => "a Boolean",
+        Found::Empty => "an empty entry",
+    }
+}
+
+pub(crate) fn entry_found(entry: &Option<Struct>) -> Found<'_> {
+    match entry {
+        Some(strct) => Found::Struct(strct),
+        None => Found::Empty,
+    }
+}
+
+/// Follow a Key to the entry it names in `parent`, if there is one
+pub(crate) fn follow<'s>(found: Found<'s>) -> Found<'s> {
+    if let Found::Value(Value::Key(key), Struct::Object { entries, .. }) = found {
+        if let Some(entry) = entries.get(key) {
+            return entry_found(entry);
+        }
+    }
+    found
+}
+
+/// An entry or property of `strct` with a name like `name`
+fn similar_name(strct: &Struct, name: &str) -> Option<String> {
+    let entries = match strct {
+        Struct::Object { entries, .. } | Struct::Table { entries, .. } => Some(entries.keys()),
+        Struct::Map { .. } => None,
+    };
+    let props = match strct {
+        Struct::Object { props, .. } | Struct::Map { props, .. } => Some(props.keys()),
+        Struct::Table { .. } => None,
+    };
+    let names = entries.into_iter().flatten().chain(props.into_iter().flatten());
+    did_you_mean(name, names.map(String::as_str)).map(str::to_string)
+}
+
+/// The entry of `parent` which a Key that failed to resolve was probably
+/// meant to name
+fn unresolved_key<'s>(key: &str, parent: &'s Struct) -> Option<&'s str> {
+    match parent {
+        Struct::Object { entries, .. } => did_you_mean(key, entries.keys().map(String::as_str)),
+        _ => None,
+    }
+}
+
+/// Take one step along a path
+pub(crate) fn step<'s>(found: Found<'s>, seg: &Segment) -> Result<Found<'s>, PathErrorKind> {
+    match (found, seg) {
+        (Found::Struct(strct), Segment::Key(name)) => {
+            let entry = match strct {
+                Struct::Object { entries, .. } | Struct::Table { entries, .. } => entries.get(name),
+                Struct::Map { .. } => None,
+            };
+            if let Some(entry) = entry {
+                return Ok(entry_found(entry));
+            }
+            let prop = match strct {
+                Struct::Object { props, .. } | Struct::Map { props, .. } => props.get(name),
+                Struct::Table { .. } => None,
+            };
+            match prop {
+                Some(val) => Ok(follow(Found::Value(val, strct))),
+                None => Err(PathErrorKind::NotFound { similar: similar_name(strct, name) }),
+            }
+        },
+        (Found::Struct(strct), Segment::Index(idx)) => {
+            match strct {
+                Struct::Object { entries, .. } | Struct::Table { entries, .. } => {
+                    entries.get_index(*idx)
+                        .map(|(_, entry)| entry_found(entry))
+                        .ok_or(PathErrorKind::OutOfRange { len: entries.len() })
+                },
+                Struct::Map { .. } => Err(PathErrorKind::NoIndices(describe(found))),
+            }
+        },
+        (Found::Value(Value::Tuple(vals), parent), Segment::Index(idx)) => {
+            vals.get(*idx)
+                .map(|val| follow(Found::Value(val, parent)))
+                .ok_or(PathErrorKind::OutOfRange { len: vals.len() })
+        },
+        _ => {
+            if let Found::Value(Value::Key(key), parent) = found {
+                if let Some(similar) = unresolved_key(key, parent) {
+                    return Err(PathErrorKind::UnresolvedKey { key: key.clone(), similar: similar.to_string() });
+                }
+            }
+            match seg {
+                Segment::Key(_) => Err(PathErrorKind::NoNames(describe(found))),
+                Segment::Index(_) => Err(PathErrorKind::NoIndices(describe(found))),
+            }
+        },
+    }
+}
+
+impl Jacl {
+    /// Look up whatever is at `path`, such as `servers.freenode.port` or
+    /// `filters[0].user`. Names are looked up among entries before
+    /// properties, indices count the entries of a Table or Object or the
+    /// elements of a Tuple, and properties holding a Key lead to the
+    /// entry they name.
+    pub fn get(&self, path: &str) -> Result<Item<'_>, PathError> {
+        let segments = parse_path(path).map_err(|msg| PathError {
+            at: path.to_string(),
+            segment: None,
+            kind: PathErrorKind::Syntax(msg),
+        })?;
+
+        let mut found = Found::Struct(self.data());
+        for (idx, seg) in segments.iter().enumerate() {
+            found = step(found, seg).map_err(|kind| PathError {
+                at: format_path(&segments[..=idx]),
+                segment: Some(seg.clone()),
+                kind,
+            })?;
+        }
+
+        Ok(found.into_item(self))
+    }
+}
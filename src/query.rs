@@ -0,0 +1,502 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Jacl;
+use crate::api::JaclStruct;
+use crate::path::{Segment, Item, Found, entry_found, follow, step, format_path};
+use crate::types::{Struct, Value};
+
+/* Queries
+ *
+ * A query is a path which may select many nodes:
+ *
+ *     servers.*.port             every server's port
+ *     ..port                     every port, at any depth
+ *     filters[1:3]               the second and third filters
+ *     filters[*].user            the user of every filter
+ *     filters[action = ignore]   every filter whose action is ignore
+ *     servers[tls]               every server with a tls property
+ *
+ * Wildcards and recursive descent visit each node once, where it is
+ * defined. Structures bound to a property, as in `country = {% %}`, are
+ * found under the property's name, and other Keys are not followed.
+ */
+
+/// A query which could not be parsed
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryError {
+    /// Where in the query the problem was found, in bytes
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid query at byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    /// A named entry or property, or an index
+    Segment(Segment),
+    /// Every child
+    Wildcard,
+    /// This node and every structure within it
+    Descend,
+    /// Children by position, from the start up to but excluding the end
+    Range(Option<usize>, Option<usize>),
+    /// Children with a property which passes the test, if there is one
+    Filter(String, Option<(Op, Value)>),
+}
+
+/// A parsed query, which can be run against many documents
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    steps: Vec<Step>,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct QueryParser<'q> {
+    input: &'q str,
+    pos: usize,
+}
+
+impl<'q> QueryParser<'q> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn error<T, M: Into<String>>(&self, message: M) -> Result<T, QueryError> {
+        Err(QueryError { offset: self.pos, message: message.into() })
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(is_name_char) {
+            self.bump();
+        }
+        self.input[start..self.pos].to_string()
+    }
+
+    fn number(&mut self) -> Option<Result<usize, QueryError>> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        let digits = &self.input[start..self.pos];
+        Some(digits.parse::<usize>().or_else(|_| self.error(format!("Index {} is too large", digits))))
+    }
+
+    /// A name, `*` or `..` after which a name or bracket may follow
+    fn segment(&mut self, steps: &mut Vec<Step>) -> Result<(), QueryError> {
+        if self.eat('*') {
+            steps.push(Step::Wildcard);
+            return Ok(());
+        }
+        let name = self.name();
+        if name.is_empty() {
+            return match self.peek() {
+                Some(c) => self.error(format!("Expected a name or '*', found '{}'", c)),
+                None => self.error("Expected a name or '*'"),
+            };
+        }
+        steps.push(Step::Segment(Segment::Key(name)));
+        Ok(())
+    }
+
+    fn bracket(&mut self) -> Result<Step, QueryError> {
+        self.skip_space();
+        let step = if self.eat('*') {
+            Step::Wildcard
+        }
+        else if self.peek().is_some_and(|c| c.is_ascii_digit() || c == ':') {
+            let start = self.number().transpose()?;
+            self.skip_space();
+            if self.eat(':') {
+                self.skip_space();
+                let end = self.number().transpose()?;
+                Step::Range(start, end)
+            }
+            else {
+                match start {
+                    Some(idx) => Step::Segment(Segment::Index(idx)),
+                    None => return self.error("Expected an index"),
+                }
+            }
+        }
+        else {
+            let name = self.name();
+            if name.is_empty() {
+                return self.error("Expected an index, range, '*' or property name");
+            }
+            self.skip_space();
+            let op = self.op()?;
+            let test = match op {
+                Some(op) => {
+                    self.skip_space();
+                    Some((op, self.literal()?))
+                },
+                None => None,
+            };
+            Step::Filter(name, test)
+        };
+        self.skip_space();
+        if !self.eat(']') {
+            return self.error("Expected ']'");
+        }
+        Ok(step)
+    }
+
+    fn op(&mut self) -> Result<Option<Op>, QueryError> {
+        let op = match self.peek() {
+            Some('=') => { self.bump(); self.eat('='); Op::Eq },
+            Some('!') => {
+                self.bump();
+                if !self.eat('=') {
+                    return self.error("Expected '=' after '!'");
+                }
+                Op::Ne
+            },
+            Some('<') => { self.bump(); if self.eat('=') { Op::Le } else { Op::Lt } },
+            Some('>') => { self.bump(); if self.eat('=') { Op::Ge } else { Op::Gt } },
+            _ => return Ok(None),
+        };
+        Ok(Some(op))
+    }
+
+    /// A value to compare against. Bare names match Keys or Strings.
+    fn literal(&mut self) -> Result<Value, QueryError> {
+        match self.peek() {
+            Some('"') => {
+                self.bump();
+                let mut string = String::new();
+                loop {
+                    match self.bump() {
+                        Some('"') => return Ok(Value::String(string)),
+                        Some('\\') => match self.bump() {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some('r') => string.push('\r'),
+                            Some(c) => string.push(c),
+                            None => return self.error("Unterminated string"),
+                        },
+                        Some(c) => string.push(c),
+                        None => return self.error("Unterminated string"),
+                    }
+                }
+            },
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let start = self.pos;
+                self.bump();
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.bump();
+                }
+                let text = &self.input[start..self.pos];
+                if let Ok(int) = text.parse::<i64>() {
+                    Ok(Value::Integer(int))
+                }
+                else if let Ok(float) = text.parse::<f64>() {
+                    Ok(Value::Float(float))
+                }
+                else {
+                    self.error(format!("'{}' is not a number", text))
+                }
+            },
+            _ => {
+                let name = self.name();
+                match name.as_str() {
+                    "" => self.error("Expected a value"),
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => Ok(Value::Key(name)),
+                }
+            },
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query, QueryError> {
+        let mut parser = QueryParser { input: query, pos: 0 };
+        let mut steps = Vec::new();
+
+        if parser.eat('.') {
+            if !parser.eat('.') {
+                return parser.error("Expected a name, '..' or '['");
+            }
+            steps.push(Step::Descend);
+            if parser.peek() != Some('[') {
+                parser.segment(&mut steps)?;
+            }
+        }
+        else if parser.peek().is_some_and(|c| c != '[') {
+            parser.segment(&mut steps)?;
+        }
+
+        while let Some(c) = parser.peek() {
+            match c {
+                '[' => {
+                    parser.bump();
+                    steps.push(parser.bracket()?);
+                },
+                '.' => {
+                    parser.bump();
+                    if parser.eat('.') {
+                        steps.push(Step::Descend);
+                        if parser.peek() == Some('[') {
+                            continue;
+                        }
+                    }
+                    parser.segment(&mut steps)?;
+                },
+                _ => return parser.error(format!("Expected '.' or '[', found '{}'", c)),
+            }
+        }
+        Ok(Query { steps })
+    }
+
+    /// Every match of this query in `jacl`, in document order
+    pub fn matches<'s>(&self, jacl: &'s Jacl) -> Matches<'s> {
+        self.run(Found::Struct(jacl.data()), jacl)
+    }
+
+    fn run<'s>(&self, root: Found<'s>, jacl: &'s Jacl) -> Matches<'s> {
+        let mut current = vec![(Vec::new(), root)];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for (path, found) in current {
+                apply(step, path, found, &mut next);
+            }
+            current = next;
+        }
+        Matches { jacl, inner: current.into_iter() }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(query: &str) -> Result<Query, QueryError> {
+        Query::parse(query)
+    }
+}
+
+/// Each child of a node with its path segment: entries in order, then
+/// properties. Anonymous entries bound to a property appear under its name.
+fn children(found: Found<'_>) -> Vec<(Segment, Found<'_>)> {
+    let (entries, props, strct) = match found {
+        Found::Struct(strct@Struct::Object { entries, props, .. }) => (Some(entries), Some(props), strct),
+        Found::Struct(strct@Struct::Table { entries, .. }) => (Some(entries), None, strct),
+        Found::Struct(strct@Struct::Map { props, .. }) => (None, Some(props), strct),
+        Found::Value(Value::Tuple(vals), parent) => {
+            return vals.iter()
+                .enumerate()
+                .map(|(idx, val)| (Segment::Index(idx), Found::Value(val, parent)))
+                .collect();
+        },
+        _ => return Vec::new(),
+    };
+
+    let is_bound = |key: &String| props.is_some_and(|props| props.values().any(|val| matches!(val, Value::Key(k) if k == key)));
+    let mut out = Vec::new();
+    for (idx, (key, entry)) in entries.into_iter().flatten().enumerate() {
+        if key.starts_with('#') {
+            if !is_bound(key) {
+                out.push((Segment::Index(idx), entry_found(entry)));
+            }
+        }
+        else {
+            out.push((Segment::Key(key.clone()), entry_found(entry)));
+        }
+    }
+    for (name, val) in props.into_iter().flatten() {
+        let found = match val {
+            Value::Key(key) if key.starts_with('#') => follow(Found::Value(val, strct)),
+            _ => Found::Value(val, strct),
+        };
+        out.push((Segment::Key(name.clone()), found));
+    }
+    out
+}
+
+/// Children by position: the entries of a Table or Object, or the elements of a Tuple
+fn positional(found: Found<'_>) -> Vec<Found<'_>> {
+    match found {
+        Found::Struct(Struct::Object { entries, .. }) | Found::Struct(Struct::Table { entries, .. }) => {
+            entries.values().map(entry_found).collect()
+        },
+        Found::Value(Value::Tuple(vals), parent) => {
+            vals.iter().map(|val| follow(Found::Value(val, parent))).collect()
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn descend<'s>(path: Vec<Segment>, found: Found<'s>, out: &mut Vec<(Vec<Segment>, Found<'s>)>) {
+    out.push((path.clone(), found));
+    for (seg, child) in children(found) {
+        if let Found::Struct(_) = child {
+            let mut path = path.clone();
+            path.push(seg);
+            descend(path, child, out);
+        }
+    }
+}
+
+fn compare(val: &Value, op: Op, literal: &Value) -> bool {
+    let ordering = match (val, literal) {
+        (Value::Key(a) | Value::String(a), Value::Key(b) | Value::String(b)) => {
+            Some(a.cmp(b))
+        },
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        _ => match (val.as_f64(), literal.as_f64()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => None,
+        },
+    };
+    match (op, ordering) {
+        (Op::Ne, None) => true,
+        (_, None) => false,
+        (Op::Eq, Some(ord)) => ord.is_eq(),
+        (Op::Ne, Some(ord)) => ord.is_ne(),
+        (Op::Lt, Some(ord)) => ord.is_lt(),
+        (Op::Le, Some(ord)) => ord.is_le(),
+        (Op::Gt, Some(ord)) => ord.is_gt(),
+        (Op::Ge, Some(ord)) => ord.is_ge(),
+    }
+}
+
+fn passes(found: Found<'_>, name: &str, test: &Option<(Op, Value)>) -> bool {
+    let prop = match found {
+        Found::Struct(Struct::Object { props, .. }) | Found::Struct(Struct::Map { props, .. }) => props.get(name),
+        _ => None,
+    };
+    match (prop, test) {
+        (Some(val), Some((op, literal))) => compare(val, *op, literal),
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+fn apply<'s>(query_step: &Step, path: Vec<Segment>, found: Found<'s>, out: &mut Vec<(Vec<Segment>, Found<'s>)>) {
+    let extend = |seg: Segment| {
+        let mut path = path.clone();
+        path.push(seg);
+        path
+    };
+    match query_step {
+        Step::Segment(seg) => {
+            if let Ok(next) = step(found, seg) {
+                out.push((extend(seg.clone()), next));
+            }
+        },
+        Step::Wildcard => {
+            out.extend(children(found).into_iter().map(|(seg, child)| (extend(seg), child)));
+        },
+        Step::Descend => descend(path, found, out),
+        Step::Range(start, end) => {
+            let items = positional(found);
+            let end = end.unwrap_or(items.len()).min(items.len());
+            let start = start.unwrap_or(0).min(end);
+            for (idx, item) in items.into_iter().enumerate().take(end).skip(start) {
+                out.push((extend(Segment::Index(idx)), item));
+            }
+        },
+        Step::Filter(name, test) => {
+            for (seg, child) in children(found) {
+                if passes(child, name, test) {
+                    out.push((extend(seg), child));
+                }
+            }
+        },
+    }
+}
+
+/// A node selected by a query, with the path which leads to it
+#[derive(Debug)]
+pub struct Match<'s> {
+    pub path: Vec<Segment>,
+    pub item: Item<'s>,
+}
+
+impl Match<'_> {
+    /// The path of this match, as accepted by `Jacl::get`
+    pub fn path_string(&self) -> String {
+        format_path(&self.path)
+    }
+}
+
+/// The matches of a query, in document order
+pub struct Matches<'s> {
+    jacl: &'s Jacl,
+    inner: std::vec::IntoIter<(Vec<Segment>, Found<'s>)>,
+}
+
+impl<'s> Iterator for Matches<'s> {
+    type Item = Match<'s>;
+
+    fn next(&mut self) -> Option<Match<'s>> {
+        self.inner.next().map(|(path, found)| Match { path, item: found.into_item(self.jacl) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Matches<'_> {}
+
+impl Jacl {
+    /// Run `query` against this document. See `Query` for the syntax.
+    pub fn query(&self, query: &str) -> Result<Matches<'_>, QueryError> {
+        Ok(Query::parse(query)?.matches(self))
+    }
+}
+
+impl<'s> JaclStruct<'s> {
+    /// Run `query` within this structure. Paths are relative to it.
+    pub fn query(&self, query: &Query) -> Matches<'s> {
+        query.run(Found::Struct(self.data()), self.jacl())
+    }
+}
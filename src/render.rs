@@ -0,0 +1,245 @@
+use std::collections::BTreeSet;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::error::{Error, Severity};
+use crate::types::Span;
+
+/// How errors are drawn for a terminal
+#[derive(Clone, Debug)]
+pub struct RenderConfig {
+    /// Colour the output with ANSI escape codes
+    pub color: bool,
+    /// Lines of source to show before and after each span
+    pub context: usize,
+    /// Columns between tab stops
+    pub tab_width: usize,
+    /// The name of the file, shown with the location of the error
+    pub filename: Option<String>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> RenderConfig {
+        RenderConfig {
+            color: false,
+            context: 0,
+            tab_width: 4,
+            filename: None,
+        }
+    }
+}
+
+/// Spans over more lines than this have their middle lines left out
+const MAX_SPAN_LINES: usize = 5;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const GUTTER: &str = "\x1b[1;34m";
+const SECONDARY: &str = "\x1b[1;36m";
+const HELP: &str = "\x1b[1;32m";
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "\x1b[1;31m",
+        Severity::Warning => "\x1b[1;33m",
+        Severity::Note => "\x1b[1;36m",
+    }
+}
+
+struct Painter {
+    color: bool,
+}
+
+impl Painter {
+    fn paint(&self, text: &str, style: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("{}{}{}", style, text, RESET)
+        }
+        else {
+            text.to_string()
+        }
+    }
+}
+
+/// The column at which the byte `idx` of `line` is displayed, counting
+/// from 0, with tabs expanded to the next tab stop
+fn display_col(line: &str, idx: usize, tab_width: usize) -> usize {
+    let mut col = 0;
+    for (offset, c) in line.char_indices() {
+        if offset >= idx {
+            break;
+        }
+        col += match c {
+            '\t' => tab_width - col % tab_width,
+            _ => c.width().unwrap_or(0),
+        };
+    }
+    col
+}
+
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut col = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - col % tab_width;
+            out.push_str(&" ".repeat(spaces));
+            col += spaces;
+        }
+        else {
+            out.push(c);
+            col += c.width().unwrap_or(0);
+        }
+    }
+    out
+}
+
+/// A span to underline, with the message written after it if any
+struct Mark<'e> {
+    span: Span,
+    last_line: usize,
+    primary: bool,
+    message: Option<&'e str>,
+}
+
+/// The lines of a mark to show. Long spans keep their first and last lines.
+fn mark_lines(mark: &Mark<'_>) -> Vec<usize> {
+    let lines = (mark.span.line..=mark.last_line).collect::<Vec<usize>>();
+    if lines.len() <= MAX_SPAN_LINES {
+        return lines;
+    }
+    let keep = MAX_SPAN_LINES / 2;
+    lines[..keep].iter().chain(&lines[lines.len() - keep..]).copied().collect()
+}
+
+/// Draw `err`, looking up lines of source with `line_at`, which gives the
+/// byte offset and text of a line counting from 1
+pub(crate) fn render<'a>(err: &Error, line_at: &dyn Fn(usize) -> Option<(usize, &'a str)>, config: &RenderConfig) -> String {
+    let painter = Painter { color: config.color };
+    let tab_width = config.tab_width.max(1);
+    let level = severity_color(err.severity());
+
+    let mut out = String::new();
+    let prefix = match err.severity() {
+        Severity::Error => String::new(),
+        Severity::Warning => painter.paint("Warning: ", level),
+        Severity::Note => painter.paint("Note: ", level),
+    };
+    let head = match err.code() {
+        Some(code) => format!("[{}]", code),
+        None => String::new(),
+    };
+    out.push_str(&prefix);
+    if !head.is_empty() {
+        out.push_str(&painter.paint(&head, level));
+        out.push(' ');
+    }
+    out.push_str(&painter.paint(err.message(), BOLD));
+    out.push('\n');
+
+    if let Some(filename) = &config.filename {
+        let location = match err.location() {
+            Some((line, col)) => format!("{}:{}:{}", filename, line, col),
+            None => filename.clone(),
+        };
+        out.push_str(&format!("{} {}\n", painter.paint("-->", GUTTER), location));
+    }
+
+    // The line a span ends on, which is the line holding its last byte
+    let last_line = |span: Span| {
+        let last = span.end.saturating_sub(1).max(span.start);
+        let mut lno = span.line;
+        while let Some((start, _)) = line_at(lno + 1) {
+            if last < start {
+                break;
+            }
+            lno += 1;
+        }
+        lno
+    };
+
+    let mut marks = Vec::new();
+    if let Some(label) = err.primary_label() {
+        marks.push(Mark { span: label.span, last_line: last_line(label.span), primary: true, message: None });
+    }
+    for label in err.secondary_labels() {
+        marks.push(Mark { span: label.span, last_line: last_line(label.span), primary: false, message: Some(&label.message) });
+    }
+    let marks = marks.into_iter()
+        .filter(|mark| line_at(mark.span.line).is_some())
+        .collect::<Vec<Mark>>();
+
+    if !marks.is_empty() {
+        let mut shown = BTreeSet::new();
+        for mark in &marks {
+            shown.extend(mark_lines(mark));
+            for lno in mark.span.line.saturating_sub(config.context).max(1)..mark.span.line {
+                shown.insert(lno);
+            }
+            for lno in mark.last_line + 1..=mark.last_line + config.context {
+                shown.insert(lno);
+            }
+        }
+        let shown = shown.into_iter()
+            .filter_map(|lno| line_at(lno).map(|line| (lno, line)))
+            .collect::<Vec<(usize, (usize, &str))>>();
+
+        let width = shown.last().map_or(1, |(lno, _)| lno.to_string().len()).max(2) + 1;
+        let blank = " ".repeat(width + 1);
+        let mut prev = None;
+        for (lno, (line_start, text)) in shown {
+            if prev.is_some_and(|prev| lno > prev + 1) {
+                out.push_str(&format!("{}\n", painter.paint("...", GUTTER)));
+            }
+            prev = Some(lno);
+
+            let gutter = painter.paint(&format!("{:<width$}|", lno, width = width), GUTTER);
+            if text.is_empty() {
+                out.push_str(&format!("{}\n", gutter));
+            }
+            else {
+                out.push_str(&format!("{} {}\n", gutter, expand_tabs(text, tab_width)));
+            }
+
+            for mark in &marks {
+                if lno < mark.span.line || lno > mark.last_line {
+                    continue;
+                }
+                let start = if lno == mark.span.line {
+                    display_col(text, mark.span.start.saturating_sub(line_start), tab_width)
+                }
+                else {
+                    0
+                };
+                let end = if lno == mark.last_line {
+                    display_col(text, mark.span.end.saturating_sub(line_start), tab_width)
+                }
+                else {
+                    display_col(text, text.len(), tab_width)
+                };
+                if end <= start && lno != mark.span.line {
+                    continue;
+                }
+                let (ch, style) = if mark.primary { ("^", level) } else { ("-", SECONDARY) };
+                let mut under = ch.repeat(usize::max(end.saturating_sub(start), 1));
+                if lno == mark.last_line {
+                    if let Some(message) = mark.message {
+                        under = format!("{} {}", under, message);
+                    }
+                }
+                out.push_str(&format!("{} {}{}\n", blank, " ".repeat(start), painter.paint(&under, style)));
+            }
+        }
+        if let Some(hint) = err.hint() {
+            out.push_str(&format!("{} {}\n", painter.paint("Hint:", BOLD), hint));
+        }
+    }
+
+    for note in err.notes() {
+        out.push_str(&format!("{} {}\n", painter.paint("Note:", BOLD), note));
+    }
+    if let Some(suggestion) = err.suggestion() {
+        out.push_str(&format!("{} {}\n", painter.paint("Help:", HELP), suggestion.message));
+    }
+    out
+}
@@ -0,0 +1,1155 @@
+use std::fmt;
+use std::iter;
+use std::str::FromStr;
+
+use indexmap::map::IndexMap;
+use regex::Regex;
+
+use crate::{Jacl, JaclError, OwnedJaclError};
+use crate::error::{Error, ErrorCode};
+use crate::path::{format_path, Segment};
+use crate::types::{Struct, Value, ValueSpan, Span, Entries, Props, Meta};
+use crate::util::did_you_mean;
+use crate::writer::{write_str, write_value};
+
+/* Schemas
+ *
+ * A schema is itself a JACL document. Its root is the rule for the root
+ * Object of the documents it checks, and its `types` entry holds named
+ * rules which may be used wherever a type is expected:
+ *
+ *     types {
+ *         server {
+ *             props {
+ *                 addr = String
+ *                 port { type = Integer; min = 1; max = 65535 }
+ *                 nick { type = String; required = false }
+ *                 ssl { type = Boolean; default = false }
+ *             }
+ *         }
+ *     }
+ *
+ *     entries {
+ *         servers { type = Object; each_entry = server }
+ *         filters { type = Table; required = false }
+ *     }
+ *
+ * A rule is an Object or Map which may hold:
+ *
+ *   type        The type of the item, or a Tuple of alternatives: Any,
+ *               String, Integer, Float, Boolean, Key, Tuple, Object, Table,
+ *               Map, Empty, or the name of a rule in `types`. Integers are
+ *               also accepted as Floats.
+ *   required    Whether the entry or property must be present (true)
+ *   default     What a missing entry or property is filled in with: a
+ *               value for a property, or a structure for an entry
+ *   min, max    Bounds on an Integer or Float
+ *   min_length, Bounds on the characters of a String or the elements of
+ *   max_length  a Tuple
+ *   one_of      A Tuple of the values allowed. A Key matches a String with
+ *               the same text.
+ *   pattern     A regular expression which must match within a String.
+ *               Use `^` and `$` to match the whole String.
+ *   elems       A Tuple of the type of each element of a Tuple
+ *   each_elem   The rule for every element of a Tuple
+ *   entries     The rule for each entry of a structure, by key
+ *   props       The rule for each property of a structure, by var
+ *   each_entry  The rule for entries not listed in `entries`, including
+ *               anonymous ones
+ *   each_prop   The rule for properties not listed in `props`
+ *   open        Whether entries and properties which are not listed are
+ *               allowed (false)
+ *
+ * Within `entries`, `props` and `types`, a rule may be given as an entry,
+ * or as a property holding its type, like `addr = String`. `each_entry`,
+ * `each_prop` and `each_elem` may be given either way too.
+ *
+ * A structure whose rule lists any entries or properties may hold only
+ * those it lists, unless the rule is open. A property whose type is a
+ * structure holds a Key naming an entry of that kind. If the entry is
+ * anonymous, as in `country = {% name = "UK" %}`, it is checked against
+ * the rule of the property.
+ *
+ * An entry or property with a default need not be present. `apply_defaults`
+ * fills in every missing one which has a default, and records that it did,
+ * so they can be told apart from those written in the document.
+ */
+
+/// What a rule allows an item to be
+#[derive(Clone, Debug, PartialEq)]
+enum Type {
+    Any,
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Key,
+    Tuple,
+    Object,
+    Table,
+    Map,
+    Empty,
+    /// A rule in `types`
+    Named(String),
+}
+
+const TYPE_NAMES: [&str; 11] = [
+    "Any", "String", "Integer", "Float", "Boolean", "Key", "Tuple", "Object", "Table", "Map", "Empty",
+];
+
+impl Type {
+    fn builtin(name: &str) -> Option<Type> {
+        match name {
+            "Any" => Some(Type::Any),
+            "String" => Some(Type::String),
+            "Integer" => Some(Type::Integer),
+            "Float" => Some(Type::Float),
+            "Boolean" => Some(Type::Boolean),
+            "Key" => Some(Type::Key),
+            "Tuple" => Some(Type::Tuple),
+            "Object" => Some(Type::Object),
+            "Table" => Some(Type::Table),
+            "Map" => Some(Type::Map),
+            "Empty" => Some(Type::Empty),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Type::Any => String::from("anything"),
+            Type::String => String::from("a String"),
+            Type::Integer => String::from("an Integer"),
+            Type::Float => String::from("a Float"),
+            Type::Boolean => String::from("a Boolean"),
+            Type::Key => String::from("a Key"),
+            Type::Tuple => String::from("a Tuple"),
+            Type::Object => String::from("an Object"),
+            Type::Table => String::from("a Table"),
+            Type::Map => String::from("a Map"),
+            Type::Empty => String::from("an empty entry"),
+            Type::Named(name) => format!("'{}'", name),
+        }
+    }
+}
+
+/// What fills in a missing entry or property
+#[derive(Clone, Debug)]
+enum DefaultItem {
+    /// The value of a property, and where it is written in the schema
+    Value(Value, Option<ValueSpan>),
+    /// The structure of an entry, and where it is written in the schema
+    Entry(Option<Box<Struct>>, Option<Span>),
+}
+
+/// The constraints on an entry, property or element
+#[derive(Clone, Debug, Default)]
+struct Rule {
+    /// Where the rule is written in the schema
+    span: Option<Span>,
+    /// The types allowed, or any type if there are none
+    types: Vec<Type>,
+    required: bool,
+    default: Option<DefaultItem>,
+    min: Option<Value>,
+    max: Option<Value>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    one_of: Option<Vec<Value>>,
+    pattern: Option<Regex>,
+    elems: Option<Vec<Rule>>,
+    each_elem: Option<Box<Rule>>,
+    entries: IndexMap<String, Rule>,
+    props: IndexMap<String, Rule>,
+    each_entry: Option<Box<Rule>>,
+    each_prop: Option<Box<Rule>>,
+    open: bool,
+}
+
+impl Rule {
+    fn new(types: Vec<Type>, span: Option<Span>) -> Rule {
+        Rule {
+            span,
+            types,
+            required: true,
+            ..Rule::default()
+        }
+    }
+
+    /// Does the rule say what a structure may contain?
+    fn lists_contents(&self) -> bool {
+        !self.entries.is_empty() || !self.props.is_empty() || self.each_entry.is_some() || self.each_prop.is_some()
+    }
+}
+
+/// Rules for the contents of JACL documents
+#[derive(Clone, Debug)]
+pub struct Schema {
+    root: Rule,
+    types: IndexMap<String, Rule>,
+}
+
+/* Reading Schemas */
+
+/// Where a rule is written, which decides what it may hold
+#[derive(Clone, Copy, PartialEq)]
+enum Site {
+    Root,
+    /// Within `types`
+    Type,
+    /// Within `entries`
+    Entry,
+    /// Within `props`
+    Prop,
+    /// Anywhere else, like `each_entry`
+    Other,
+}
+
+impl Site {
+    /// Is this the rule of an entry or property?
+    fn is_field(self) -> bool {
+        matches!(self, Site::Entry | Site::Prop)
+    }
+}
+
+const RULE_FIELDS: [&str; 16] = [
+    "type", "required", "default", "min", "max", "min_length", "max_length", "one_of", "pattern",
+    "elems", "each_elem", "entries", "props", "each_entry", "each_prop", "open",
+];
+
+pub(crate) fn entries_of(strct: &Struct) -> Option<&Entries> {
+    match strct {
+        Struct::Object { entries, .. } | Struct::Table { entries, .. } => Some(entries),
+        Struct::Map { .. } => None,
+    }
+}
+
+pub(crate) fn props_of(strct: &Struct) -> Option<&Props> {
+    match strct {
+        Struct::Object { props, .. } | Struct::Map { props, .. } => Some(props),
+        Struct::Table { .. } => None,
+    }
+}
+
+/// Is the anonymous entry `key` bound to a property of `strct`?
+pub(crate) fn is_bound(strct: &Struct, key: &str) -> bool {
+    props_of(strct).is_some_and(|props| props.values().any(|val| matches!(val, Value::Key(k) if k == key)))
+}
+
+/// Where an entry is defined - its key, or its structure if it is anonymous
+fn entry_anchor(parent: &Struct, key: &str, entry: &Option<Struct>) -> Option<Span> {
+    parent.meta().entries.get(key)
+        .and_then(|spans| spans.first()).copied()
+        .or_else(|| entry.as_ref().and_then(|strct| strct.meta().span))
+}
+
+struct Loader {
+    /// The names of the rules in `types`
+    names: Vec<String>,
+    errors: Vec<Error>,
+}
+
+impl Loader {
+    fn invalid(&mut self, span: Option<Span>, message: String, hint: &str) {
+        self.errors.push(match span {
+            Some(span) => Error::spanned(ErrorCode::InvalidSchema, message, span, hint.to_string()),
+            None => Error::basic(ErrorCode::InvalidSchema, message),
+        });
+    }
+
+    /// Report an unknown name, suggesting the most similar of `names`
+    fn unknown<'n, I: IntoIterator<Item = &'n str>>(&mut self, name: &str, span: Option<Span>, names: I, message: String, hint: &str) {
+        let similar = did_you_mean(name, names).map(str::to_string);
+        self.invalid(span, message, hint);
+        if let (Some(similar), Some(span)) = (similar, span) {
+            if let Some(err) = self.errors.pop() {
+                self.errors.push(err.with_suggestion(format!("Did you mean {}?", similar), span, similar));
+            }
+        }
+    }
+
+    fn type_named(&mut self, name: &str, span: Option<Span>) -> Option<Type> {
+        if let Some(ty) = Type::builtin(name) {
+            return Some(ty);
+        }
+        if self.names.iter().any(|known| known == name) {
+            return Some(Type::Named(name.to_string()));
+        }
+
+        let names = TYPE_NAMES.iter().map(|name| name.to_string()).chain(self.names.iter().cloned()).collect::<Vec<String>>();
+        self.unknown(name, span, names.iter().map(String::as_str), format!("No type named '{}'", name),
+                     "This is not a type or the name of a rule in types");
+        None
+    }
+
+    /// A type, or a Tuple of alternative types
+    fn types(&mut self, val: &Value, vspan: Option<&ValueSpan>) -> Vec<Type> {
+        let span = vspan.map(|vs| vs.span);
+        match val {
+            Value::Key(name) => self.type_named(name, span).into_iter().collect(),
+            Value::Tuple(vals) => {
+                let mut types = Vec::new();
+                for (idx, val) in vals.iter().enumerate() {
+                    let espan = vspan.and_then(|vs| vs.elems.get(idx));
+                    match val {
+                        Value::Key(name) => types.extend(self.type_named(name, espan.map(|vs| vs.span))),
+                        _ => self.invalid(espan.map(|vs| vs.span).or(span), String::from("Expected the name of a type"),
+                                          "Types are written as names, like String"),
+                    }
+                }
+                types
+            },
+            _ => {
+                self.invalid(span, String::from("Expected a type or a Tuple of types"), "Types are written as names, like String");
+                Vec::new()
+            },
+        }
+    }
+
+    /// A rule given by a property - its type, or an anonymous rule bound to it
+    fn rule_value(&mut self, parent: &Struct, val: &Value, vspan: Option<&ValueSpan>, site: Site) -> Rule {
+        let span = vspan.map(|vs| vs.span);
+        if let Value::Key(key) = val {
+            if key.starts_with('#') {
+                return match entries_of(parent).and_then(|entries| entries.get(key)) {
+                    Some(Some(strct)) => self.rule(strct, site, span),
+                    _ => Rule::new(Vec::new(), span),
+                };
+            }
+        }
+        let types = self.types(val, vspan);
+        Rule::new(types, span)
+    }
+
+    /// A rule given by an entry, which has no constraints if it is empty
+    fn rule_entry(&mut self, entry: &Option<Struct>, site: Site, span: Option<Span>) -> Rule {
+        match entry {
+            Some(strct) => self.rule(strct, site, span),
+            None => Rule::new(Vec::new(), span),
+        }
+    }
+
+    /// The rules listed in `entries`, `props` or `types`
+    fn block(&mut self, entry: &Option<Struct>, site: Site, span: Option<Span>) -> IndexMap<String, Rule> {
+        let mut rules = IndexMap::new();
+        let strct = match entry {
+            Some(strct @ Struct::Object { .. }) | Some(strct @ Struct::Map { .. }) => strct,
+            Some(Struct::Table { .. }) => {
+                self.invalid(span, String::from("Rules must be listed in an Object or Map"), "This is a Table");
+                return rules;
+            },
+            None => return rules,
+        };
+
+        for (key, entry) in entries_of(strct).into_iter().flatten() {
+            let span = entry_anchor(strct, key, entry);
+            if !key.starts_with('#') {
+                let rule = self.rule_entry(entry, site, span);
+                rules.insert(key.clone(), rule);
+            }
+            else if !is_bound(strct, key) {
+                self.invalid(span, String::from("Rules must be named"), "Give this rule a key");
+            }
+        }
+        for (name, val) in props_of(strct).into_iter().flatten() {
+            let vspan = strct.meta().props.get(name).map(|prop| &prop.value);
+            let rule = self.rule_value(strct, val, vspan, site);
+            rules.insert(name.clone(), rule);
+        }
+        rules
+    }
+
+    fn boolean(&mut self, name: &str, val: &Value, span: Option<Span>) -> bool {
+        match val {
+            Value::Boolean(boolean) => *boolean,
+            _ => {
+                self.invalid(span, format!("{} must be true or false", name), "Expected a Boolean");
+                false
+            },
+        }
+    }
+
+    fn number(&mut self, name: &str, val: &Value, span: Option<Span>) -> Option<Value> {
+        match val {
+            Value::Integer(_) | Value::Float(_) => Some(val.clone()),
+            _ => {
+                self.invalid(span, format!("{} must be a number", name), "Expected an Integer or Float");
+                None
+            },
+        }
+    }
+
+    fn length(&mut self, name: &str, val: &Value, span: Option<Span>) -> Option<usize> {
+        match val {
+            Value::Integer(integer) if *integer >= 0 => Some(*integer as usize),
+            _ => {
+                self.invalid(span, format!("{} must be an Integer of at least 0", name), "Expected a length");
+                None
+            },
+        }
+    }
+
+    /// The rule written as `strct`
+    fn rule(&mut self, strct: &Struct, site: Site, span: Option<Span>) -> Rule {
+        let mut rule = Rule::new(Vec::new(), span.or(strct.meta().span));
+        if let Struct::Table { .. } = strct {
+            self.invalid(rule.span, String::from("A rule must be an Object or Map"), "This is a Table");
+            return rule;
+        }
+        let meta = strct.meta();
+
+        for (name, val) in props_of(strct).into_iter().flatten() {
+            let prop = meta.props.get(name);
+            let vspan = prop.map(|prop| &prop.value);
+            let span = vspan.map(|vs| vs.span);
+            match name.as_str() {
+                "type" => rule.types = self.types(val, vspan),
+                "required" if site.is_field() => rule.required = self.boolean(name, val, span),
+                "default" if site == Site::Prop => rule.default = self.default_value(strct, val, vspan),
+                "default" if site == Site::Entry => match val {
+                    Value::Key(key) if key.starts_with('#') => {
+                        let entry = entries_of(strct).and_then(|entries| entries.get(key)).cloned().flatten();
+                        rule.default = Some(DefaultItem::Entry(entry.map(Box::new), span));
+                    },
+                    _ => self.invalid(span, String::from("The default of an entry must be a structure"),
+                                      "Write the default as an entry, like default { ... }"),
+                },
+                "min" => rule.min = self.number(name, val, span),
+                "max" => rule.max = self.number(name, val, span),
+                "min_length" => rule.min_length = self.length(name, val, span),
+                "max_length" => rule.max_length = self.length(name, val, span),
+                "one_of" => match val {
+                    Value::Tuple(vals) => rule.one_of = Some(vals.clone()),
+                    _ => self.invalid(span, String::from("one_of must be a Tuple"), "List the values allowed in a Tuple"),
+                },
+                "pattern" => match val {
+                    Value::String(pattern) => match Regex::new(pattern) {
+                        Ok(regex) => rule.pattern = Some(regex),
+                        Err(err) => {
+                            // The last line of the message says what is wrong
+                            let err = err.to_string();
+                            let reason = err.lines().last().unwrap_or_default().trim_start_matches("error: ");
+                            self.invalid(span, String::from("Invalid pattern"), reason);
+                        },
+                    },
+                    _ => self.invalid(span, String::from("pattern must be a String"), "Expected a regular expression"),
+                },
+                "elems" => match val {
+                    Value::Tuple(vals) => {
+                        let elems = vals.iter().enumerate().map(|(idx, val)| {
+                            let espan = vspan.and_then(|vs| vs.elems.get(idx));
+                            Rule::new(self.types(val, espan), espan.map(|vs| vs.span))
+                        }).collect();
+                        rule.elems = Some(elems);
+                    },
+                    _ => self.invalid(span, String::from("elems must be a Tuple"), "Give the type of each element in a Tuple"),
+                },
+                "each_elem" => rule.each_elem = Some(Box::new(self.rule_value(strct, val, vspan, Site::Other))),
+                "each_entry" => rule.each_entry = Some(Box::new(self.rule_value(strct, val, vspan, Site::Other))),
+                "each_prop" => rule.each_prop = Some(Box::new(self.rule_value(strct, val, vspan, Site::Other))),
+                "open" => rule.open = self.boolean(name, val, span),
+                _ => self.unknown_field(name, prop.map(|prop| prop.name), site),
+            }
+        }
+
+        for (key, entry) in entries_of(strct).into_iter().flatten() {
+            let span = entry_anchor(strct, key, entry);
+            match key.as_str() {
+                "entries" => rule.entries = self.block(entry, Site::Entry, span),
+                "props" => rule.props = self.block(entry, Site::Prop, span),
+                "default" if site == Site::Entry => rule.default = Some(DefaultItem::Entry(entry.clone().map(Box::new), span)),
+                "default" if site == Site::Prop => {
+                    self.invalid(span, String::from("The default of a property must be a value"),
+                                 "Write the default as a property, like default = 1");
+                },
+                "each_elem" => rule.each_elem = Some(Box::new(self.rule_entry(entry, Site::Other, span))),
+                "each_entry" => rule.each_entry = Some(Box::new(self.rule_entry(entry, Site::Other, span))),
+                "each_prop" => rule.each_prop = Some(Box::new(self.rule_entry(entry, Site::Other, span))),
+                "types" if site == Site::Root => {},
+                _ if key.starts_with('#') => {
+                    if !is_bound(strct, key) {
+                        self.invalid(span, String::from("Unexpected anonymous structure in a rule"), "Remove this structure");
+                    }
+                },
+                _ => self.unknown_field(key, span, site),
+            }
+        }
+
+        let named = rule.types.iter().any(|ty| matches!(ty, Type::Named(_)));
+        if named && rule.lists_contents() {
+            self.invalid(rule.span, String::from("A rule which names a type cannot also list entries or properties"),
+                         "Move the entries and properties into the named rule");
+        }
+        rule
+    }
+
+    /// The default of a property, which cannot be a structure
+    fn default_value(&mut self, parent: &Struct, val: &Value, vspan: Option<&ValueSpan>) -> Option<DefaultItem> {
+        match val {
+            Value::Key(key) if key.starts_with('#') && entries_of(parent).is_some_and(|entries| entries.contains_key(key)) => {
+                self.invalid(vspan.map(|vs| vs.span), String::from("The default of a property must be a value"),
+                             "A property cannot be filled in with a structure");
+                None
+            },
+            _ => Some(DefaultItem::Value(val.clone(), vspan.cloned())),
+        }
+    }
+
+    fn unknown_field(&mut self, name: &str, span: Option<Span>, site: Site) {
+        if name == "required" || name == "default" {
+            let message = if name == "required" { "Only entries and properties can be required" }
+                          else { "Only entries and properties can have defaults" };
+            self.invalid(span, String::from(message), &format!("{} is only allowed in rules within entries or props", name));
+            return;
+        }
+        let mut fields = RULE_FIELDS.iter().copied()
+                                    .filter(|field| !matches!(*field, "required" | "default") || site.is_field())
+                                    .collect::<Vec<&str>>();
+        if site == Site::Root {
+            fields.push("types");
+        }
+        self.unknown(name, span, fields, format!("Unknown field '{}' in a rule", name), "This is not a field of a rule");
+    }
+
+    /// Report rules in `types` which are defined in terms of themselves
+    fn check_cycles(&mut self, types: &IndexMap<String, Rule>) {
+        for (name, rule) in types {
+            let mut seen = vec![name.as_str()];
+            let mut todo = rule.types.iter().collect::<Vec<&Type>>();
+            while let Some(ty) = todo.pop() {
+                if let Type::Named(next) = ty {
+                    if next == name {
+                        self.invalid(rule.span, format!("Type '{}' is defined in terms of itself", name),
+                                     "Give this rule a type which is not itself");
+                        break;
+                    }
+                    if !seen.contains(&next.as_str()) {
+                        seen.push(next);
+                        todo.extend(types.get(next).into_iter().flat_map(|rule| &rule.types));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Schema {
+    /// Read a schema written as JACL
+    pub fn parse(input: &str) -> Result<Schema, OwnedJaclError> {
+        let jacl = crate::read_string(input).map_err(|err| err.into_owned())?;
+        Schema::from_jacl(&jacl).map_err(|err| err.into_owned())
+    }
+
+    /// Read a schema from a parsed document. Errors point into its source.
+    pub fn from_jacl(jacl: &Jacl) -> Result<Schema, JaclError<'_>> {
+        let root = jacl.data();
+        let types_entry = entries_of(root).and_then(|entries| entries.get("types"));
+        let types_span = types_entry.and_then(|entry| entry_anchor(root, "types", entry));
+
+        let mut names = Vec::new();
+        if let Some(Some(types)) = types_entry {
+            names.extend(entries_of(types).into_iter().flatten().map(|(key, _)| key.clone()).filter(|key| !key.starts_with('#')));
+            names.extend(props_of(types).into_iter().flatten().map(|(name, _)| name.clone()));
+        }
+        let mut loader = Loader { names, errors: Vec::new() };
+
+        let types = match types_entry {
+            Some(entry) => loader.block(entry, Site::Type, types_span),
+            None => IndexMap::new(),
+        };
+        let root = loader.rule(root, Site::Root, None);
+        loader.check_cycles(&types);
+
+        // Defaults are checked once every rule they may refer to is known
+        if loader.errors.is_empty() {
+            let mut checker = Checker { types: &types, found: Vec::new() };
+            for rule in types.values().chain(iter::once(&root)) {
+                checker.defaults(rule);
+            }
+            loader.errors.extend(checker.found.into_iter().map(|violation| violation.error));
+        }
+
+        if loader.errors.is_empty() {
+            Ok(Schema { root, types })
+        }
+        else {
+            Err(jacl.error_from(loader.errors))
+        }
+    }
+}
+
+impl FromStr for Schema {
+    type Err = OwnedJaclError;
+
+    fn from_str(input: &str) -> Result<Schema, OwnedJaclError> {
+        Schema::parse(input)
+    }
+}
+
+/* Validation */
+
+/// Something in a document which its schema does not allow
+#[derive(Clone, Debug)]
+pub struct Violation {
+    path: Vec<Segment>,
+    error: Error,
+}
+
+impl Violation {
+    /// The path to the item which broke a rule. It is empty for the root.
+    pub fn path(&self) -> &[Segment] {
+        &self.path
+    }
+
+    /// The error to report, located in the source of the document
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    pub fn message(&self) -> &str {
+        self.error.message()
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.error.span()
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// Something being checked against a rule
+#[derive(Clone, Copy)]
+enum Node<'d> {
+    Entry {
+        entry: Option<&'d Struct>,
+        /// Where problems with the entry are reported
+        anchor: Option<Span>,
+    },
+    Value {
+        val: &'d Value,
+        vspan: Option<&'d ValueSpan>,
+        /// The structure the value was bound in, for resolving Keys
+        parent: &'d Struct,
+    },
+}
+
+impl<'d> Node<'d> {
+    fn anchor(&self) -> Option<Span> {
+        match self {
+            Node::Entry { anchor, .. } => *anchor,
+            Node::Value { vspan, .. } => vspan.map(|vs| vs.span),
+        }
+    }
+
+    /// The entry a Key names, if this is a Key naming one
+    fn resolve(&self) -> Option<(&'d str, &'d Option<Struct>)> {
+        if let Node::Value { val: Value::Key(key), parent, .. } = self {
+            return entries_of(parent).and_then(|entries| entries.get(key)).map(|entry| (key.as_str(), entry));
+        }
+        None
+    }
+
+    fn describe(&self) -> String {
+        let strct = match self {
+            Node::Entry { entry, .. } => *entry,
+            Node::Value { val: Value::Key(key), .. } if key.starts_with('#') => {
+                match self.resolve() {
+                    Some((_, entry)) => entry.as_ref(),
+                    None => return String::from("a Key"),
+                }
+            },
+            Node::Value { val, .. } => {
+                return match val {
+                    Value::Integer(_) | Value::Float(_) => format!("the {} {}", val.type_name(), write_value(val).unwrap_or_default()),
+                    _ => format!("a {}", val.type_name()),
+                };
+            },
+        };
+        match strct {
+            Some(Struct::Object { .. }) => String::from("an Object"),
+            Some(Struct::Table { .. }) => String::from("a Table"),
+            Some(Struct::Map { .. }) => String::from("a Map"),
+            None => String::from("an empty entry"),
+        }
+    }
+}
+
+fn kind_fits(ty: &Type, entry: Option<&Struct>) -> bool {
+    matches!((ty, entry), (Type::Any, _) |
+                          (Type::Object, Some(Struct::Object { .. })) |
+                          (Type::Table, Some(Struct::Table { .. })) |
+                          (Type::Map, Some(Struct::Map { .. })) |
+                          (Type::Empty, None))
+}
+
+/// Does `val` come before `bound`?
+fn below(val: &Value, bound: &Value) -> bool {
+    match (val, bound) {
+        (Value::Integer(a), Value::Integer(b)) => a < b,
+        _ => match (val.as_f64(), bound.as_f64()) {
+            (Some(a), Some(b)) => a < b,
+            _ => false,
+        },
+    }
+}
+
+fn one_of(val: &Value, allowed: &Value) -> bool {
+    match (val, allowed) {
+        (Value::Key(a) | Value::String(a), Value::Key(b) | Value::String(b)) => a == b,
+        (Value::Integer(_) | Value::Float(_), Value::Integer(_) | Value::Float(_)) => {
+            !below(val, allowed) && !below(allowed, val)
+        },
+        _ => val == allowed,
+    }
+}
+
+fn count(n: usize, unit: &str) -> String {
+    if n == 1 { format!("1 {}", unit) } else { format!("{} {}s", n, unit) }
+}
+
+fn list(vals: &[Value]) -> String {
+    vals.iter().map(|val| write_value(val).unwrap_or_default()).collect::<Vec<String>>().join(", ")
+}
+
+struct Checker<'a> {
+    types: &'a IndexMap<String, Rule>,
+    found: Vec<Violation>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, path: &[Segment], code: ErrorCode, span: Option<Span>, message: String, hint: &str) {
+        let message = if path.is_empty() { message } else { format!("{}: {}", format_path(path), message) };
+        let error = match span {
+            Some(span) => Error::spanned(code, message, span, hint.to_string()),
+            None => Error::basic(code, message),
+        };
+        self.found.push(Violation { path: path.to_vec(), error });
+    }
+
+    /// Suggest a similar name for the last item reported, as a String if
+    /// `quote` is set
+    fn suggest<'n, I: IntoIterator<Item = &'n str>>(&mut self, name: &str, span: Option<Span>, names: I, quote: bool) {
+        let similar = match did_you_mean(name, names) {
+            Some(similar) if quote => match write_str(similar) {
+                Ok(string) => string,
+                Err(_) => return,
+            },
+            Some(similar) => similar.to_string(),
+            None => return,
+        };
+        if let (Some(span), Some(violation)) = (span, self.found.last_mut()) {
+            violation.error = violation.error.clone().with_suggestion(format!("Did you mean {}?", similar), span, similar);
+        }
+    }
+
+    /// Does `node` fit `ty`, without breaking any rules?
+    fn fits(&self, path: &mut Vec<Segment>, node: Node<'_>, ty: &Type) -> bool {
+        match (ty, node) {
+            (Type::Named(name), _) => {
+                let mut trial = Checker { types: self.types, found: Vec::new() };
+                if let Some(rule) = self.types.get(name) {
+                    trial.check(path, node, rule);
+                }
+                trial.found.is_empty()
+            },
+            (_, Node::Entry { entry, .. }) => kind_fits(ty, entry),
+            (Type::Any, _) => true,
+            (Type::Object | Type::Table | Type::Map | Type::Empty, _) => {
+                node.resolve().is_some_and(|(_, entry)| kind_fits(ty, entry.as_ref()))
+            },
+            (_, Node::Value { val, .. }) => {
+                matches!((ty, val), (Type::String, Value::String(_)) |
+                                    (Type::Integer, Value::Integer(_)) |
+                                    (Type::Float, Value::Float(_) | Value::Integer(_)) |
+                                    (Type::Boolean, Value::Boolean(_)) |
+                                    (Type::Tuple, Value::Tuple(_))) ||
+                matches!((ty, val), (Type::Key, Value::Key(key)) if !key.starts_with('#'))
+            },
+        }
+    }
+
+    /// Could `node` be of type `ty`, judging only by its kind?
+    fn could_be(&self, node: Node<'_>, ty: &Type) -> bool {
+        match ty {
+            Type::Named(name) => match self.types.get(name) {
+                Some(rule) => rule.types.is_empty() || rule.types.iter().any(|ty| self.could_be(node, ty)),
+                None => false,
+            },
+            _ => self.fits(&mut Vec::new(), node, ty),
+        }
+    }
+
+    /// Check `node` against `rule`, recording everything it breaks
+    fn check(&mut self, path: &mut Vec<Segment>, node: Node<'_>, rule: &Rule) {
+        let types = self.types;
+        match rule.types.as_slice() {
+            [] => {},
+            [Type::Named(name)] => {
+                if let Some(named) = types.get(name) {
+                    self.check(path, node, named);
+                }
+            },
+            alternatives => {
+                if !alternatives.iter().any(|ty| self.fits(path, node, ty)) {
+                    // If only one alternative is of the right kind, what
+                    // is wrong is best explained by that one
+                    let candidates = alternatives.iter().filter(|ty| self.could_be(node, ty)).collect::<Vec<&Type>>();
+                    if let [Type::Named(name)] = candidates.as_slice() {
+                        if let Some(named) = types.get(name) {
+                            self.check(path, node, named);
+                            return;
+                        }
+                    }
+                    let expected = alternatives.iter().map(Type::describe).collect::<Vec<String>>().join(" or ");
+                    let hint = format!("Make this {}", expected);
+                    self.report(path, ErrorCode::WrongType, node.anchor(),
+                                format!("Expected {}, found {}", expected, node.describe()), &hint);
+                    return;
+                }
+            },
+        }
+
+        match node {
+            Node::Entry { entry: Some(strct), anchor } => {
+                if rule.lists_contents() {
+                    self.contents(path, strct, anchor, rule);
+                }
+            },
+            Node::Entry { entry: None, .. } => {},
+            Node::Value { val, vspan, parent } => self.value(path, val, vspan, parent, rule),
+        }
+    }
+
+    fn value(&mut self, path: &mut Vec<Segment>, val: &Value, vspan: Option<&ValueSpan>, parent: &Struct, rule: &Rule) {
+        let span = vspan.map(|vs| vs.span);
+        let text = write_value(val).unwrap_or_default();
+
+        if let Some(min) = &rule.min {
+            if below(val, min) {
+                let min = write_value(min).unwrap_or_default();
+                self.report(path, ErrorCode::OutOfRange, span, format!("{} is less than the minimum of {}", text, min),
+                            &format!("This must be at least {}", min));
+            }
+        }
+        if let Some(max) = &rule.max {
+            if below(max, val) {
+                let max = write_value(max).unwrap_or_default();
+                self.report(path, ErrorCode::OutOfRange, span, format!("{} is more than the maximum of {}", text, max),
+                            &format!("This must be at most {}", max));
+            }
+        }
+
+        let length = match val {
+            Value::String(string) => Some((string.chars().count(), "character")),
+            Value::Tuple(vals) => Some((vals.len(), "element")),
+            _ => None,
+        };
+        if let Some((length, unit)) = length {
+            if let Some(min) = rule.min_length.filter(|min| length < *min) {
+                let hint = format!("This must have at least {}", count(min, unit));
+                self.report(path, ErrorCode::WrongLength, span,
+                            format!("Too short, with {} rather than at least {}", count(length, unit), min), &hint);
+            }
+            if let Some(max) = rule.max_length.filter(|max| length > *max) {
+                let hint = format!("This must have at most {}", count(max, unit));
+                self.report(path, ErrorCode::WrongLength, span,
+                            format!("Too long, with {} rather than at most {}", count(length, unit), max), &hint);
+            }
+        }
+
+        if let Some(allowed) = &rule.one_of {
+            if !allowed.iter().any(|allowed| one_of(val, allowed)) {
+                let hint = format!("Expected one of {}", list(allowed));
+                self.report(path, ErrorCode::NotOneOf, span, format!("{} is not one of {}", text, list(allowed)), &hint);
+                if let Some(name) = val.as_str().or_else(|| val.as_key()) {
+                    let names = allowed.iter().filter_map(|allowed| match allowed {
+                        Value::Key(name) | Value::String(name) => Some(name.as_str()),
+                        _ => None,
+                    });
+                    self.suggest(name, span, names, val.as_str().is_some());
+                }
+            }
+        }
+
+        if let (Some(pattern), Value::String(string)) = (&rule.pattern, val) {
+            if !pattern.is_match(string) {
+                let hint = format!("This must match {}", pattern.as_str());
+                self.report(path, ErrorCode::NoPatternMatch, span, format!("{} does not match {}", text, pattern.as_str()), &hint);
+            }
+        }
+
+        if let Value::Tuple(vals) = val {
+            if let Some(elems) = &rule.elems {
+                if vals.len() != elems.len() {
+                    let hint = format!("This must have {}", count(elems.len(), "element"));
+                    self.report(path, ErrorCode::WrongLength, span,
+                                format!("Expected a Tuple of {}, found {}", count(elems.len(), "element"), vals.len()), &hint);
+                }
+            }
+            for (idx, val) in vals.iter().enumerate() {
+                let elem = rule.elems.as_ref().and_then(|elems| elems.get(idx)).or(rule.each_elem.as_deref());
+                if let Some(elem) = elem {
+                    let vspan = vspan.and_then(|vs| vs.elems.get(idx));
+                    path.push(Segment::Index(idx));
+                    self.check(path, Node::Value { val, vspan, parent }, elem);
+                    path.pop();
+                }
+            }
+        }
+
+        // A property with a rule for its contents must name a structure.
+        // An anonymous one is checked here, since it belongs to the
+        // property rather than its parent.
+        if rule.lists_contents() {
+            let node = Node::Value { val, vspan, parent };
+            match node.resolve() {
+                Some((key, Some(strct))) => {
+                    if key.starts_with('#') {
+                        self.contents(path, strct, span, rule);
+                    }
+                },
+                Some((_, None)) => {},
+                None => {
+                    self.report(path, ErrorCode::WrongType, span, format!("Expected a structure, found {}", node.describe()),
+                                "Make this a Key naming an entry");
+                    if let Some(key) = val.as_key() {
+                        self.suggest(key, span, entries_of(parent).into_iter().flat_map(|entries| entries.keys().map(String::as_str)), false);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Check each default within `rule` against the rule it is the default of
+    fn defaults(&mut self, rule: &Rule) {
+        match &rule.default {
+            // A Key names an entry of the document, so cannot be checked yet
+            None | Some(DefaultItem::Value(Value::Key(_), _)) => {},
+            Some(DefaultItem::Value(val, vspan)) => {
+                let parent = Struct::Map { props: Props::new(), meta: Meta::default() };
+                self.check(&mut Vec::new(), Node::Value { val, vspan: vspan.as_ref(), parent: &parent }, rule);
+            },
+            Some(DefaultItem::Entry(entry, anchor)) => {
+                self.check(&mut Vec::new(), Node::Entry { entry: entry.as_deref(), anchor: *anchor }, rule);
+            },
+        }
+
+        let inner = rule.entries.values().chain(rule.props.values())
+                        .chain(rule.each_entry.as_deref())
+                        .chain(rule.each_prop.as_deref())
+                        .chain(rule.each_elem.as_deref());
+        for inner in inner {
+            self.defaults(inner);
+        }
+    }
+
+    /// Check the entries and properties of `strct`
+    fn contents(&mut self, path: &mut Vec<Segment>, strct: &Struct, anchor: Option<Span>, rule: &Rule) {
+        let entries = entries_of(strct);
+        let props = props_of(strct);
+        let meta = strct.meta();
+
+        for (key, entry_rule) in &rule.entries {
+            if entry_rule.required && entry_rule.default.is_none() && !entries.is_some_and(|entries| entries.contains_key(key)) {
+                self.report(path, ErrorCode::MissingItem, anchor, format!("Missing entry '{}'", key),
+                            &format!("This needs an entry named '{}'", key));
+            }
+        }
+        for (name, prop_rule) in &rule.props {
+            if prop_rule.required && prop_rule.default.is_none() && !props.is_some_and(|props| props.contains_key(name)) {
+                self.report(path, ErrorCode::MissingItem, anchor, format!("Missing property '{}'", name),
+                            &format!("This needs a property named '{}'", name));
+            }
+        }
+
+        for (idx, (key, entry)) in entries.into_iter().flatten().enumerate() {
+            let anonymous = key.starts_with('#');
+            if anonymous && is_bound(strct, key) {
+                continue;
+            }
+            let anchor = entry_anchor(strct, key, entry);
+            let seg = if anonymous { Segment::Index(idx) } else { Segment::Key(key.clone()) };
+            let entry_rule = if anonymous { None } else { rule.entries.get(key) };
+            path.push(seg);
+            match entry_rule.or(rule.each_entry.as_deref()) {
+                Some(entry_rule) => self.check(path, Node::Entry { entry: entry.as_ref(), anchor }, entry_rule),
+                None if !rule.open => {
+                    if anonymous {
+                        self.report(path, ErrorCode::UnexpectedItem, anchor, String::from("Unexpected anonymous entry"),
+                                    "The schema does not allow this entry");
+                    }
+                    else {
+                        self.report(path, ErrorCode::UnexpectedItem, anchor, format!("Unexpected entry '{}'", key),
+                                    "The schema does not allow this entry");
+                        self.suggest(key, anchor, rule.entries.keys().map(String::as_str), false);
+                    }
+                },
+                None => {},
+            }
+            path.pop();
+        }
+
+        for (name, val) in props.into_iter().flatten() {
+            let prop = meta.props.get(name);
+            let vspan = prop.map(|prop| &prop.value);
+            path.push(Segment::Key(name.clone()));
+            match rule.props.get(name).or(rule.each_prop.as_deref()) {
+                Some(prop_rule) => self.check(path, Node::Value { val, vspan, parent: strct }, prop_rule),
+                None if !rule.open => {
+                    let span = prop.map(|prop| prop.name);
+                    self.report(path, ErrorCode::UnexpectedItem, span, format!("Unexpected property '{}'", name),
+                                "The schema does not allow this property");
+                    self.suggest(name, span, rule.props.keys().map(String::as_str), false);
+                },
+                None => {},
+            }
+            path.pop();
+        }
+    }
+}
+
+impl Schema {
+    /// Every way in which `jacl` breaks the rules, in document order
+    pub fn check(&self, jacl: &Jacl) -> Vec<Violation> {
+        let mut checker = Checker { types: &self.types, found: Vec::new() };
+        // Whatever is wrong with the document as a whole is reported at its start
+        let start = Span { start: 0, end: 0, line: 1, col: 1 };
+        checker.check(&mut Vec::new(), Node::Entry { entry: Some(jacl.data()), anchor: Some(start) }, &self.root);
+        checker.found
+    }
+
+    /// Check `jacl`, returning anything it breaks as errors located in its
+    /// source
+    pub fn validate<'s>(&self, jacl: &'s Jacl) -> Result<(), JaclError<'s>> {
+        let found = self.check(jacl);
+        if found.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(jacl.error_from(found.into_iter().map(|violation| violation.error).collect()))
+        }
+    }
+}
+
+/* Defaults */
+
+/// Copies of defaults from a schema, marked in `meta` as filled in
+fn default_entries(entries: &Entries, meta: &mut Meta) -> Entries {
+    meta.default_entries.extend(entries.keys().cloned());
+    entries.iter().map(|(key, entry)| (key.clone(), entry.as_ref().map(as_default))).collect()
+}
+
+fn default_props(props: &Props, meta: &mut Meta) -> Props {
+    meta.default_props.extend(props.keys().cloned());
+    props.clone()
+}
+
+/// A copy of a default from a schema, without its Spans, with everything
+/// in it marked as filled in from a default
+fn as_default(strct: &Struct) -> Struct {
+    let mut meta = Meta::default();
+    match strct {
+        Struct::Object { entries, props, .. } => {
+            let entries = default_entries(entries, &mut meta);
+            let props = default_props(props, &mut meta);
+            Struct::Object { entries, props, meta }
+        },
+        Struct::Table { entries, .. } => Struct::Table { entries: default_entries(entries, &mut meta), meta },
+        Struct::Map { props, .. } => Struct::Map { props: default_props(props, &mut meta), meta },
+    }
+}
+
+impl Schema {
+    /// The rule which says what `strct` may hold, following named types
+    fn contents_rule<'r>(&'r self, strct: &Struct, mut rule: &'r Rule) -> &'r Rule {
+        let checker = Checker { types: &self.types, found: Vec::new() };
+        let node = Node::Entry { entry: Some(strct), anchor: None };
+        // Named types are not defined in terms of themselves, so this ends
+        loop {
+            let named = rule.types.iter().filter(|ty| matches!(ty, Type::Named(_)));
+            let chosen = named.clone().find(|ty| checker.fits(&mut Vec::new(), node, ty))
+                                      .or_else(|| named.clone().find(|ty| checker.could_be(node, ty)));
+            match chosen.and_then(|ty| match ty { Type::Named(name) => self.types.get(name), _ => None }) {
+                Some(named) => rule = named,
+                None => return rule,
+            }
+        }
+    }
+
+    /// Fill in the defaults within `strct`, which is held to `rule`
+    fn fill(&self, strct: &mut Struct, rule: &Rule) {
+        let rule = self.contents_rule(strct, rule);
+        let (entries, props, meta) = match strct {
+            Struct::Object { entries, props, meta } => (Some(entries), Some(props), meta),
+            Struct::Table { entries, meta } => (Some(entries), None, meta),
+            Struct::Map { props, meta } => (None, Some(props), meta),
+        };
+
+        // Anonymous entries bound to properties are held to the rules of
+        // the properties
+        let mut bound = IndexMap::new();
+        if let Some(props) = props {
+            for (name, prop_rule) in &rule.props {
+                if let (Some(DefaultItem::Value(val, _)), false) = (&prop_rule.default, props.contains_key(name)) {
+                    props.insert(name.clone(), val.clone());
+                    meta.default_props.insert(name.clone());
+                }
+            }
+            for (name, val) in props.iter() {
+                if let Value::Key(key) = val {
+                    if key.starts_with('#') {
+                        bound.insert(key.clone(), rule.props.get(name).or(rule.each_prop.as_deref()));
+                    }
+                }
+            }
+        }
+
+        if let Some(entries) = entries {
+            for (key, entry_rule) in &rule.entries {
+                if let (Some(DefaultItem::Entry(entry, _)), false) = (&entry_rule.default, entries.contains_key(key)) {
+                    entries.insert(key.clone(), entry.as_deref().map(as_default));
+                    meta.default_entries.insert(key.clone());
+                }
+            }
+            for (key, entry) in entries.iter_mut() {
+                let entry_rule = match bound.get(key) {
+                    Some(prop_rule) => *prop_rule,
+                    None if key.starts_with('#') => rule.each_entry.as_deref(),
+                    None => rule.entries.get(key).or(rule.each_entry.as_deref()),
+                };
+                if let (Some(entry_rule), Some(inner)) = (entry_rule, entry) {
+                    self.fill(inner, entry_rule);
+                }
+            }
+        }
+    }
+
+    /// Fill in every missing entry and property which has a default.
+    /// Those filled in are marked, so `EntryStruct::entry_origin` and
+    /// `PropertyStruct::property_origin` can tell them apart from those
+    /// written in the document.
+    pub fn apply_defaults(&self, jacl: &mut Jacl) {
+        self.fill(jacl.data_mut(), &self.root);
+    }
+
+    /// Read a document, fill in its defaults and check it against the
+    /// schema
+    pub fn load<'src>(&self, input: &'src str) -> Result<Jacl, JaclError<'src>> {
+        let mut jacl = crate::read_string(input)?;
+        self.apply_defaults(&mut jacl);
+        let found = self.check(&jacl);
+        if found.is_empty() {
+            Ok(jacl)
+        }
+        else {
+            let errors = found.into_iter().map(|violation| violation.error).collect();
+            Err(JaclError::from_errors(errors, input, jacl.lines().clone()))
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::ser::{self, Serialize};
+
+use crate::Jacl;
+use crate::dom::{Structure, DomError};
+use crate::path::{format_path, Segment};
+use crate::types::{Value, StructKind};
+use crate::writer::{write_document, WriteError};
+
+/* Serde Serializer
+ *
+ * Rust structs become Objects, with their structured fields as entries
+ * and the rest as properties. Maps become Maps when all of their values
+ * are plain values, and Objects otherwise. Sequences of structures
+ * become Tables of anonymous entries, and sequences of values become
+ * Tuples. Enum variants with data are written as a structure holding a
+ * single item named after the variant. Unit variants are written as a
+ * String, except in a Table, where they are a structure holding just an
+ * empty entry named after the variant.
+ */
+
+/// An error from serializing a value as JACL
+#[derive(Clone, Debug, PartialEq)]
+pub struct SerError {
+    message: String,
+    path: Vec<Segment>,
+}
+
+impl SerError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The path to the value which could not be serialized
+    pub fn path(&self) -> &[Segment] {
+        &self.path
+    }
+
+    /// Record that the error happened within `seg`
+    fn within(mut self, seg: Segment) -> SerError {
+        self.path.insert(0, seg);
+        self
+    }
+}
+
+impl fmt::Display for SerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", format_path(&self.path))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SerError {}
+
+impl ser::Error for SerError {
+    fn custom<T: fmt::Display>(msg: T) -> SerError {
+        SerError { message: msg.to_string(), path: Vec::new() }
+    }
+}
+
+impl From<DomError> for SerError {
+    fn from(err: DomError) -> SerError {
+        ser::Error::custom(err)
+    }
+}
+
+impl From<WriteError> for SerError {
+    fn from(err: WriteError) -> SerError {
+        ser::Error::custom(err)
+    }
+}
+
+fn unsupported(what: &str) -> SerError {
+    ser::Error::custom(format!("JACL cannot represent {}", what))
+}
+
+/// Write `value` as JACL on a single line
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, SerError> {
+    let jacl = to_jacl(value)?;
+    Ok(write_document(jacl.data(), false)?)
+}
+
+/// Write `value` as JACL, with each item on its own line
+pub fn to_string_pretty<T: Serialize + ?Sized>(value: &T) -> Result<String, SerError> {
+    let jacl = to_jacl(value)?;
+    Ok(write_document(jacl.data(), true)?)
+}
+
+/// Convert `value` to a document, which must be a struct or a map
+pub fn to_jacl<T: Serialize + ?Sized>(value: &T) -> Result<Jacl, SerError> {
+    match value.serialize(Serializer)? {
+        Node::Struct(root) => Ok(Jacl::from_structure(&root)?),
+        _ => Err(ser::Error::custom("Only structs and maps can be written as documents")),
+    }
+}
+
+/// A serialized value, before it is placed within its parent
+enum Node {
+    Value(Value),
+    Struct(Structure),
+    /// A unit, written as an entry without a structure
+    Empty,
+    /// A unit variant, written as a String of its name
+    Variant(&'static str),
+    /// A None, which is left out
+    Absent,
+}
+
+impl Node {
+    fn into_value(self, within: &str) -> Result<Value, SerError> {
+        match self {
+            Node::Value(val) => Ok(val),
+            Node::Variant(name) => Ok(Value::String(name.to_string())),
+            Node::Struct(_) => Err(unsupported(&format!("a structure within {}", within))),
+            Node::Empty => Err(unsupported(&format!("a unit within {}", within))),
+            Node::Absent => Err(unsupported(&format!("None within {}", within))),
+        }
+    }
+}
+
+/// Place `node` under `name` in `strct`, as an entry or a property
+fn place(strct: &mut Structure, name: &str, node: Node) -> Result<(), SerError> {
+    let result = match node {
+        Node::Value(val) => strct.insert_property(name, val).map(|_| ()),
+        Node::Variant(variant) => strct.insert_property(name, variant).map(|_| ()),
+        Node::Struct(entry) => strct.insert_entry(name, Some(entry)),
+        Node::Empty => strct.insert_entry(name, None),
+        Node::Absent => Ok(()),
+    };
+    result.map_err(|err| SerError::from(err).within(Segment::Key(name.to_string())))
+}
+
+/// A structure holding just `node`, named after an enum variant
+fn variant(name: &str, node: Node) -> Result<Node, SerError> {
+    let mut strct = Structure::object();
+    place(&mut strct, name, node)?;
+    Ok(Node::Struct(strct))
+}
+
+/// Turn a sequence into a Tuple of values or a Table of structures
+fn sequence(elems: Vec<Node>) -> Result<Node, SerError> {
+    if let Some(idx) = elems.iter().position(|elem| matches!(elem, Node::Empty | Node::Absent)) {
+        return Err(unsupported("a unit or None within a sequence").within(Segment::Index(idx)));
+    }
+    if elems.iter().all(|elem| matches!(elem, Node::Value(_) | Node::Variant(_))) && !elems.is_empty() {
+        let vals = elems.into_iter()
+                        .map(|elem| elem.into_value("a Tuple"))
+                        .collect::<Result<Vec<Value>, SerError>>()?;
+        return Ok(Node::Value(Value::Tuple(vals)));
+    }
+    let mut table = Structure::table();
+    for (idx, elem) in elems.into_iter().enumerate() {
+        match elem {
+            Node::Struct(entry) => table.push_entry(entry)?,
+            // A String cannot sit in a Table, so the variant is written as data variants are
+            Node::Variant(name) => {
+                let mut entry = Structure::object();
+                place(&mut entry, name, Node::Empty)?;
+                table.push_entry(entry)?;
+            },
+            _ => {
+                let err = unsupported("a sequence mixing values and structures, since a Table may only contain structures");
+                return Err(err.within(Segment::Index(idx)));
+            },
+        }
+    }
+    Ok(Node::Struct(table))
+}
+
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, SerError> {
+        Ok(Node::Value(Value::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, SerError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, SerError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, SerError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, SerError> {
+        if v < 0 {
+            return Err(unsupported(&format!("the negative number {}", v)));
+        }
+        Ok(Node::Value(Value::Integer(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, SerError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, SerError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, SerError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, SerError> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Err(unsupported(&format!("{}, which is too large for an Integer", v))),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, SerError> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, SerError> {
+        if !v.is_finite() || (v.is_sign_negative() && v != 0.0) {
+            return Err(unsupported(&format!("the Float {}", v)));
+        }
+        Ok(Node::Value(Value::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, SerError> {
+        Ok(Node::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, SerError> {
+        Ok(Node::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Node, SerError> {
+        Err(unsupported("raw bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Node, SerError> {
+        Ok(Node::Absent)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, SerError> {
+        Ok(Node::Empty)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, SerError> {
+        Ok(Node::Empty)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Node, SerError> {
+        Ok(Node::Variant(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Node, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Node, SerError> {
+        let node = value.serialize(Serializer)?;
+        self::variant(variant, node)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SerError> {
+        Ok(SeqSerializer { variant: None, elems: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<SeqSerializer, SerError> {
+        Ok(SeqSerializer { variant: Some(variant), elems: Vec::with_capacity(len) })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, SerError> {
+        Ok(MapSerializer { variant: None, items: Vec::new(), key: None, is_struct: false })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<MapSerializer, SerError> {
+        Ok(MapSerializer { variant: None, items: Vec::new(), key: None, is_struct: true })
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<MapSerializer, SerError> {
+        Ok(MapSerializer { variant: Some(variant), items: Vec::new(), key: None, is_struct: true })
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    elems: Vec<Node>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        let idx = self.elems.len();
+        let node = value.serialize(Serializer).map_err(|err| err.within(Segment::Index(idx)))?;
+        self.elems.push(node);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Node, SerError> {
+        let node = sequence(self.elems)?;
+        match self.variant {
+            Some(name) => variant(name, node),
+            None => Ok(node),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, SerError> {
+        self.finish()
+    }
+}
+
+struct MapSerializer {
+    variant: Option<&'static str>,
+    items: Vec<(String, Node)>,
+    key: Option<String>,
+    /// Structs are always Objects, while maps of values become Maps
+    is_struct: bool,
+}
+
+impl MapSerializer {
+    fn field<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), SerError> {
+        let node = value.serialize(Serializer).map_err(|err| err.within(Segment::Key(key.clone())))?;
+        self.items.push((key, node));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Node, SerError> {
+        let values_only = self.items.iter().all(|(_, node)| matches!(node, Node::Value(_) | Node::Variant(_) | Node::Absent));
+        let kind = if !self.is_struct && values_only { StructKind::Map } else { StructKind::Object };
+        let mut strct = Structure::new(kind);
+        for (name, node) in self.items {
+            place(&mut strct, &name, node)?;
+        }
+        match self.variant {
+            Some(name) => variant(name, Node::Struct(strct)),
+            None => Ok(Node::Struct(strct)),
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerError> {
+        match key.serialize(Serializer)? {
+            Node::Value(Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            },
+            Node::Variant(key) => {
+                self.key = Some(key.to_string());
+                Ok(())
+            },
+            _ => Err(unsupported("map keys other than strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Node, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerError> {
+        self.field(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Node, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Node;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerError> {
+        self.field(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Node, SerError> {
+        self.finish()
+    }
+}
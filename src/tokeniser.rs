@@ -1,5 +1,6 @@
 use crate::Lines;
-use crate::error::{Error};
+use crate::error::{Error, ErrorCode};
+use crate::types::Span;
 use std::iter;
 
 #[derive(Clone, Debug)]
@@ -36,25 +37,34 @@ pub enum TokVal<'src> {
 #[derive(Clone, Debug)]
 pub struct Token<'src> {
     pub val: TokVal<'src>,
-    lptr: usize,
-    rptr: usize,
+    start: usize,
+    end: usize,
     pub lno: usize,
     pub col: usize,
     pub len: usize,
 }
 
 impl<'src> Token<'src> {
-    fn new(val: TokVal<'src>, lptr: usize, rptr: usize,
+    pub(crate) fn new(val: TokVal<'src>, start: usize, end: usize,
            lno: usize, col: usize, len: usize) -> Token<'src> {
         Token {
             val,
-            lptr,
-            rptr,
+            start,
+            end,
             lno,
             col,
             len,
         }
     }
+
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.end,
+            line: self.lno,
+            col: self.col,
+        }
+    }
 }
 
 enum State {
@@ -65,8 +75,9 @@ enum State {
     InFloat,
     SeenBrace,
     SeenPct,
+    InComment,
 
-    Unrecoverable,
+    Recovering,
 }
 
 fn unambiguous_symbol<'src>(c: char) -> Option<TokVal<'src>> { 
@@ -90,15 +101,19 @@ fn unambiguous_symbol<'src>(c: char) -> Option<TokVal<'src>> {
     }
 }
 
-pub fn tokenise<'src>(input: &'src str) -> (Lines, Result<Vec<Token<'src>>, Vec<Error<'src>>>) {
-    let mut lines: Lines = Vec::new(); // Spans of Lines - byte offsets
+/// Split the input into Tokens, alongside the spans of its lines.
+///
+/// Malformed input is skipped up to the next whitespace or newline, so the
+/// returned Tokens cover everything that could be understood, and every
+/// lexical error is returned.
+pub fn tokenise<'src>(input: &'src str) -> (Lines, Vec<Token<'src>>, Vec<Error>) {
+    let mut lines: Lines = Vec::new(); // Spans of Lines - byte offsets, end exclusive
     let mut lineptr: usize = 0;                      // Start of current Line - byte offset
 
     let mut errors: Vec<Error> = Vec::new();
 
     let mut toks = Vec::new();       // Built Tokens
     let mut lptr: usize = 0;         // Left end of current Token - byte offset
-    let mut rptr: usize = 0;         // Right end of current Token - byte offset
     let mut state = State::Neutral;  // Current Tokeniser State
 
     let mut lcol: usize = 1;         // Left end of current Token - char count
@@ -113,124 +128,126 @@ pub fn tokenise<'src>(input: &'src str) -> (Lines, Result<Vec<Token<'src>>, Vec<
             lineptr = offset; 
         }
 
-        // Check if we need to change state *before* updating `rptr`
+        // Check if we need to change state *before* handling this char
         match &state {
-            State::InBare => {
-                if !(c.is_alphanumeric() || c == '_') {
-                    let buf = &input[lptr..=rptr];
-                    if buf.chars().all(|c| c.is_ascii_digit()) {
-                        if c == '.' {
-                            state = State::InFloat;
-                        }
-                        else {
-                            if let Ok(val) = str::parse::<i64>(buf) {
-                                toks.push(Token::new(TokVal::Integer(val), lptr, rptr,
-                                                     lno, lcol, col - lcol));
-                            }
-                            else {
-                                let tok = Token::new(TokVal::Fault, lptr, rptr,
-                                                     lno, lcol, col - lcol);
-                                println!("{}, {}", lcol, col);
-                                errors.push(Error::detailed(100, String::from("Could not parse number as 64-byte signed Integer"),
-                                                            tok, String::from("This value may be too large")));
-                            }
-                            state = State::Neutral;
-                        }
+            State::InBare if !(c.is_alphanumeric() || c == '_') => {
+                let buf = &input[lptr..offset];
+                if buf.chars().all(|c| c.is_ascii_digit()) {
+                    if c == '.' {
+                        state = State::InFloat;
                     }
                     else {
-                        match buf {
-                            "true" => {
-                                toks.push(Token::new(TokVal::Boolean(true), lptr, rptr,
-                                                     lno, lcol, col - lcol));
-                            },
-                            "false" => {
-                                toks.push(Token::new(TokVal::Boolean(false), lptr, rptr,
-                                                     lno, lcol, col - lcol));
-                            },
-                            _ => {
-                                toks.push(Token::new(TokVal::Name(buf), lptr, rptr,
-                                                     lno, lcol, col - lcol));
-                            }
+                        if let Ok(val) = str::parse::<i64>(buf) {
+                            toks.push(Token::new(TokVal::Integer(val), lptr, offset,
+                                                 lno, lcol, col - lcol));
+                        }
+                        else {
+                            let tok = Token::new(TokVal::Fault, lptr, offset,
+                                                 lno, lcol, col - lcol);
+                            errors.push(Error::detailed(ErrorCode::IntegerTooLarge, String::from("Could not parse number as 64-byte signed Integer"),
+                                                        tok, String::from("This value may be too large"))
+                                              .with_note(format!("Integers can be at most {}", i64::MAX)));
                         }
                         state = State::Neutral;
                     }
                 }
-            },
-            State::InFloat => {
-                if !c.is_ascii_digit() {
-                    let buf = &input[lptr..=rptr];
-                    if let Ok(val) = str::parse::<f64>(buf) {
-                        toks.push(Token::new(TokVal::Float(val), lptr, rptr,
-                                             lno, lcol, col - lcol));
-                    }
-                    else {
-                        let tok = Token::new(TokVal::Fault, lptr, rptr,
-                                             lno, lcol, col - lcol);
-
-                        errors.push(Error::detailed(101, String::from("Could not parse number as 64-byte Float"),
-                                                    tok, String::from("This value may be too large")));
+                else {
+                    match buf {
+                        "true" => {
+                            toks.push(Token::new(TokVal::Boolean(true), lptr, offset,
+                                                 lno, lcol, col - lcol));
+                        },
+                        "false" => {
+                            toks.push(Token::new(TokVal::Boolean(false), lptr, offset,
+                                                 lno, lcol, col - lcol));
+                        },
+                        _ => {
+                            toks.push(Token::new(TokVal::Name(buf), lptr, offset,
+                                                 lno, lcol, col - lcol));
+                        }
                     }
                     state = State::Neutral;
                 }
             },
-            State::SeenBrace => {
-                if c != '%' {
-                    assert!(lptr == rptr);
-                    toks.push(Token::new(TokVal::LBrace, lptr, rptr,
+            State::InFloat if !c.is_ascii_digit() => {
+                let buf = &input[lptr..offset];
+                if let Ok(val) = str::parse::<f64>(buf) {
+                    toks.push(Token::new(TokVal::Float(val), lptr, offset,
                                          lno, lcol, col - lcol));
-                    state = State::Neutral; 
                 }
+                else {
+                    let tok = Token::new(TokVal::Fault, lptr, offset,
+                                         lno, lcol, col - lcol);
+
+                    errors.push(Error::detailed(ErrorCode::FloatTooLarge, String::from("Could not parse number as 64-byte Float"),
+                                                tok, String::from("This value may be too large")));
+                }
+                state = State::Neutral;
+            },
+            // The end of the input also ends a comment
+            State::InComment if offset == input.len() => {
+                state = State::Neutral;
+            },
+            State::StartString | State::InString { .. } if offset == input.len() => {
+                let tok = Token::new(TokVal::Fault, lptr, offset,
+                                     lno, lcol, col - lcol);
+
+                let end = Span { start: offset, end: offset, line: lno, col };
+                errors.push(Error::detailed(ErrorCode::UnterminatedString, String::from("Unterminated String"),
+                                            tok, String::from("Close this string with '\"'"))
+                                  .with_suggestion("Add '\"' at the end of the input", end, "\""));
+                state = State::Neutral;
+            },
+            State::SeenBrace if c != '%' => {
+                toks.push(Token::new(TokVal::LBrace, lptr, lptr + 1,
+                                     lno, lcol, col - lcol));
+                state = State::Neutral; 
             }
             _ => {},
         }
 
         if c == '\n' {
+            // A newline always ends the current line of input, so it is where
+            // we resynchronise after any lexical error on that line
             if let State::SeenPct = state {
-               let tok = Token::new(TokVal::Fault, lptr, rptr,
+               let tok = Token::new(TokVal::Fault, lptr, offset,
                                      lno, lcol, col - lcol);
 
-                errors.push(Error::detailed(104, String::from("% was followed by a newline"),
+                errors.push(Error::detailed(ErrorCode::PercentBeforeNewline, String::from("% was followed by a newline"),
                                             tok, String::from("Expected '}'"))); 
-
-                state = State::Unrecoverable;
             }
-            else if !matches!(state, State::Neutral) {
-                let tok = Token::new(TokVal::Fault, lptr, rptr,
+            else if !matches!(state, State::Neutral | State::InComment | State::Recovering) {
+                let tok = Token::new(TokVal::Fault, offset, offset + 1,
                                      lno, col, 1);
 
-                errors.push(Error::detailed(105, String::from("Unexpected newline"),
+                errors.push(Error::detailed(ErrorCode::UnexpectedNewline, String::from("Unexpected newline"),
                                             tok, String::from("Remove this linebreak")));
-
-                state = State::Unrecoverable;
-
-            }
-            else {
-                toks.push(Token::new(TokVal::Break, rptr, offset,
-                                     lno, col, 1));
             }
-            lines.push((lineptr, rptr));
+            state = State::Neutral;
+            toks.push(Token::new(TokVal::Break, offset, offset + 1,
+                                 lno, col, 1));
+            lines.push((lineptr, offset));
             lno += 1;
             col = 1;
             continue;
         }
 
-        rptr = offset;
-
         // Enter the string now we've got past the initial quote
         if let State::StartString = state {
             lptr = offset;
-            lcol = col;
             state = State::InString { escaped: false };
         }
 
-        // Check if we need to change state *after* updating `rptr`
+        // Check if we need to change state *after* handling this char
         match &state {
             State::Neutral => {
                 lptr = offset;
                 lcol = col;
 
                 if let Some(symbol) = unambiguous_symbol(c) {
-                    toks.push(Token::new(symbol, lptr, rptr,
+                    // The Break appended at the end of the input has no width
+                    let end = usize::min(offset + 1, input.len());
+                    toks.push(Token::new(symbol, lptr, end,
                                          lno, lcol, 1));
                 }
                 else if c == '{' {
@@ -242,25 +259,28 @@ pub fn tokenise<'src>(input: &'src str) -> (Lines, Result<Vec<Token<'src>>, Vec<
                 else if c == '"' {
                     state = State::StartString;
                 }
+                else if c == '#' {
+                    state = State::InComment;
+                }
                 else if c.is_alphanumeric() || c == '_' {
                     state = State::InBare;
                 }
                 else if !c.is_whitespace() {
-                    let tok = Token::new(TokVal::Fault, lptr, rptr,
+                    let tok = Token::new(TokVal::Fault, lptr, offset + c.len_utf8(),
                                          lno, lcol, 1);
 
-                    errors.push(Error::detailed(102, String::from("Unexpected Character"),
+                    errors.push(Error::detailed(ErrorCode::UnexpectedCharacter, String::from("Unexpected Character"),
                                                 tok, String::from("This character could not be understood")));
 
-                    state = State::Unrecoverable;
+                    state = State::Recovering;
                 }
             },
             State::InString { escaped } => {
                 if !escaped {
                     if c == '"' {
-                        let buf = &input[lptr..rptr];
-                        toks.push(Token::new(TokVal::String(buf), lptr, rptr,
-                                             lno, lcol, col - lcol));
+                        let buf = &input[lptr..offset];
+                        toks.push(Token::new(TokVal::String(buf), lptr - 1, offset + 1,
+                                             lno, lcol, col - lcol + 1));
                         state = State::Neutral;
                     }
                     else if c == '\\' {
@@ -273,37 +293,45 @@ pub fn tokenise<'src>(input: &'src str) -> (Lines, Result<Vec<Token<'src>>, Vec<
             },
             State::SeenBrace => {
                 assert!(c == '%');
-                toks.push(Token::new(TokVal::LBracePct, lptr, rptr,
-                                     lno, lcol, col - lcol));
+                toks.push(Token::new(TokVal::LBracePct, lptr, offset + 1,
+                                     lno, lcol, col - lcol + 1));
                 state = State::Neutral;
             },
             State::SeenPct => {
                 if c == '}' {
-                    toks.push(Token::new(TokVal::RBracePct, lptr, rptr,
-                                         lno, lcol, col - lcol));
+                    toks.push(Token::new(TokVal::RBracePct, lptr, offset + 1,
+                                         lno, lcol, col - lcol + 1));
                     state = State::Neutral;
                 }
                 else {
-                    let tok = Token::new(TokVal::Fault, lptr, rptr,
-                                         lno, lcol, col - lcol);
+                    let tok = Token::new(TokVal::Fault, lptr, offset + c.len_utf8(),
+                                         lno, lcol, col - lcol + 1);
 
-                    errors.push(Error::detailed(103, String::from("% was not followed by }"),
+                    errors.push(Error::detailed(ErrorCode::PercentWithoutBrace, String::from("% was not followed by }"),
                                                 tok, String::from("Unparseable character pair here"))); 
 
-                    state = State::Unrecoverable;
+                    state = if c.is_whitespace() { State::Neutral } else { State::Recovering };
                 }
             },
+            // Skip the rest of a malformed word so that we report
+            // each lexical error only once
+            State::Recovering if c.is_whitespace() => {
+                state = State::Neutral;
+            },
             _ => {},
         }
 
         col += 1;
     }
 
-    if errors.len() > 0 {
-        (lines, Err(errors))
-    }
-    else {
-        (lines, Ok(toks))
+    // A lexical error at the very end of the input swallows its Break
+    if let State::Recovering = state {
+        toks.push(Token::new(TokVal::Break, input.len(), input.len(),
+                             lno, col - 1, 1));
     }
+
+    lines.push((lineptr, input.len()));
+
+    (lines, toks, errors)
 }
 
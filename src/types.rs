@@ -1,6 +1,10 @@
+use std::convert::TryFrom;
+use std::fmt;
+
 use indexmap::map::IndexMap;
+use indexmap::set::IndexSet;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     Key(String),
     Tuple(Vec<Value>),
@@ -11,12 +15,344 @@ pub enum Value {
     Boolean(bool),
 }
 
+impl Value {
+    /// The name of this kind of value, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Key(_) => "Key",
+            Value::Tuple(_) => "Tuple",
+            Value::String(_) => "String",
+            Value::Integer(_) => "Integer",
+            Value::Float(_) => "Float",
+            Value::Boolean(_) => "Boolean",
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    /// The value of a Float, or of an Integer converted to a Float
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(float) => Some(*float),
+            Value::Integer(integer) => Some(*integer as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(boolean) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn as_tuple(&self) -> Option<&[Value]> {
+        match self {
+            Value::Tuple(vals) => Some(vals),
+            _ => None,
+        }
+    }
+
+    /// The name of the entry a Key refers to
+    pub fn as_key(&self) -> Option<&str> {
+        match self {
+            Value::Key(key) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+/// A Value could not be converted to a Rust type
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConversionError {
+    WrongType { expected: &'static str, actual: &'static str },
+    /// An Integer too large or small for the target type
+    OutOfRange { target: &'static str, value: i64 },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::WrongType { expected, actual } => write!(f, "Expected {}, found {}", expected, actual),
+            ConversionError::OutOfRange { target, value } => write!(f, "Integer {} does not fit in {}", value, target),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+fn wrong_type(expected: &'static str, val: &Value) -> ConversionError {
+    ConversionError::WrongType { expected, actual: val.type_name() }
+}
+
+impl<'v> TryFrom<&'v Value> for &'v str {
+    type Error = ConversionError;
+
+    fn try_from(val: &'v Value) -> Result<&'v str, ConversionError> {
+        val.as_str().ok_or_else(|| wrong_type("String", val))
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = ConversionError;
+
+    fn try_from(val: &Value) -> Result<String, ConversionError> {
+        val.as_str().map(String::from).ok_or_else(|| wrong_type("String", val))
+    }
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = ConversionError;
+
+    fn try_from(val: &Value) -> Result<bool, ConversionError> {
+        val.as_bool().ok_or_else(|| wrong_type("Boolean", val))
+    }
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(val: &Value) -> Result<f64, ConversionError> {
+        val.as_f64().ok_or_else(|| wrong_type("Float", val))
+    }
+}
+
+impl TryFrom<&Value> for f32 {
+    type Error = ConversionError;
+
+    fn try_from(val: &Value) -> Result<f32, ConversionError> {
+        val.as_f64().map(|float| float as f32).ok_or_else(|| wrong_type("Float", val))
+    }
+}
+
+macro_rules! integer_conversions {
+    ($($int:ty),*) => {$(
+        impl TryFrom<&Value> for $int {
+            type Error = ConversionError;
+
+            fn try_from(val: &Value) -> Result<$int, ConversionError> {
+                let integer = val.as_i64().ok_or_else(|| wrong_type("Integer", val))?;
+                <$int>::try_from(integer).map_err(|_| {
+                    ConversionError::OutOfRange { target: stringify!($int), value: integer }
+                })
+            }
+        }
+    )*};
+}
+
+integer_conversions!(i64, i32, i16, i8, u64, u32, u16, u8, usize);
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Value {
+        Value::String(string.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Value {
+        Value::String(string)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(boolean: bool) -> Value {
+        Value::Boolean(boolean)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(float: f64) -> Value {
+        Value::Float(float)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(float: f32) -> Value {
+        Value::Float(f64::from(float))
+    }
+}
+
+macro_rules! integer_values {
+    ($($int:ty),*) => {$(
+        impl From<$int> for Value {
+            fn from(integer: $int) -> Value {
+                Value::Integer(i64::from(integer))
+            }
+        }
+    )*};
+}
+
+integer_values!(i64, i32, i16, i8, u32, u16, u8);
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(vals: Vec<T>) -> Value {
+        Value::Tuple(vals.into_iter().map(Into::into).collect())
+    }
+}
+
+/// A region of the source text.
+///
+/// `start` and `end` are byte offsets, with `end` exclusive.
+/// `line` and `col` give the position of `start`, counting from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    /// The smallest Span covering both `self` and `other`
+    pub fn to(&self, other: &Span) -> Span {
+        let first = if self.start <= other.start { self } else { other };
+        Span {
+            start: usize::min(self.start, other.start),
+            end: usize::max(self.end, other.end),
+            line: first.line,
+            col: first.col,
+        }
+    }
+}
+
+/// The Span of a Value, and of each of its elements if it is a Tuple
+#[derive(Clone, Debug)]
+pub struct ValueSpan {
+    pub span: Span,
+    pub elems: Vec<ValueSpan>,
+}
+
+impl ValueSpan {
+    pub fn new(span: Span) -> ValueSpan {
+        ValueSpan {
+            span,
+            elems: Vec::new(),
+        }
+    }
+}
+
+/// A definition of a property which has since been overridden
+#[derive(Clone, Debug)]
+pub struct Definition {
+    pub var: Span,
+    pub value: Value,
+    pub value_span: ValueSpan,
+}
+
+#[derive(Clone, Debug)]
+pub struct PropSpan {
+    pub name: Span,
+    pub value: ValueSpan,
+    pub history: Vec<Definition>,
+}
+
+impl PropSpan {
+    pub fn new(name: Span, value: ValueSpan) -> PropSpan {
+        PropSpan {
+            name,
+            value,
+            history: Vec::new(),
+        }
+    }
+}
+
+/// Source locations of a Struct and of everything defined within it
+#[derive(Clone, Debug, Default)]
+pub struct Meta {
+    pub span: Option<Span>,
+    /// Every definition of each entry, in the order they were made
+    pub entries: IndexMap<String, Vec<Span>>,
+    pub props: IndexMap<String, PropSpan>,
+    /// Entries which were filled in from the defaults of a schema
+    pub default_entries: IndexSet<String>,
+    /// Properties which were filled in from the defaults of a schema
+    pub default_props: IndexSet<String>,
+}
+
+impl Meta {
+    pub fn define_entry(&mut self, key: &str, span: Span) {
+        self.entries.entry(key.to_string()).or_default().push(span);
+    }
+
+    /// Record a new definition of a property. `old` is the value it
+    /// replaces, if the property was already defined.
+    pub fn define_prop(&mut self, name: &str, mut new: PropSpan, old: Option<Value>) {
+        if let (Some(old), Some(prev)) = (old, self.props.get(name)) {
+            let mut history = prev.history.clone();
+            history.push(Definition {
+                var: prev.name,
+                value: old,
+                value_span: prev.value.clone(),
+            });
+            history.append(&mut new.history);
+            new.history = history;
+        }
+        self.props.insert(name.to_string(), new);
+    }
+}
+
+/// The three kinds of data structure
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StructKind {
+    Object,
+    Table,
+    Map,
+}
+
 pub type Entries = IndexMap<String, Option<Struct>>;
 pub type Props = IndexMap<String, Value>;
 
 #[derive(Clone, Debug)]
 pub enum Struct {
-    Object { entries: Entries, props: Props},
-    Table { entries: Entries },
-    Map { props: Props },
+    Object { entries: Entries, props: Props, meta: Meta },
+    Table { entries: Entries, meta: Meta },
+    Map { props: Props, meta: Meta },
+}
+
+/// Structs are equal if they hold the same data, wherever it was defined
+impl PartialEq for Struct {
+    fn eq(&self, other: &Struct) -> bool {
+        match (self, other) {
+            (Struct::Object { entries, props, .. }, Struct::Object { entries: o_entries, props: o_props, .. }) => {
+                entries == o_entries && props == o_props
+            },
+            (Struct::Table { entries, .. }, Struct::Table { entries: o_entries, .. }) => {
+                entries == o_entries
+            },
+            (Struct::Map { props, .. }, Struct::Map { props: o_props, .. }) => {
+                props == o_props
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Struct {
+    pub fn meta(&self) -> &Meta {
+        match self {
+            Struct::Object { meta, .. } |
+            Struct::Table { meta, .. } |
+            Struct::Map { meta, .. } => meta,
+        }
+    }
+
+    pub fn meta_mut(&mut self) -> &mut Meta {
+        match self {
+            Struct::Object { meta, .. } |
+            Struct::Table { meta, .. } |
+            Struct::Map { meta, .. } => meta,
+        }
+    }
 }
@@ -1,7 +1,50 @@
 use crate::Lines;
-use crate::tokeniser::Token;
 
-pub fn token_line<'src>(input: &'src str, lines: &Lines, token: &Token<'src>) -> &'src str {
-    let line_ptrs = lines[token.lno - 1];
-    &input[line_ptrs.0..=line_ptrs.1]
+/// The text of line `lno`, counting from 1, without its newline
+pub fn line_text<'src>(input: &'src str, lines: &Lines, lno: usize) -> Option<&'src str> {
+    let line_ptrs = lines.get(lno.checked_sub(1)?)?;
+    Some(&input[line_ptrs.0..line_ptrs.1])
+}
+
+/// The number of single character insertions, deletions, substitutions
+/// and swaps of neighbours needed to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+
+    // Three rows of the distance matrix, for the row before last, the
+    // last row and this row
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev = (0..=b.len()).collect::<Vec<usize>>();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// The candidate most like `name`, if any is close enough to be a typo of
+/// it. Anonymous keys are never suggested.
+pub fn did_you_mean<'c, I: IntoIterator<Item = &'c str>>(name: &str, candidates: I) -> Option<&'c str> {
+    let limit = usize::max(1, name.chars().count() / 3);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        if candidate.starts_with('#') || candidate == name {
+            continue;
+        }
+        let dist = edit_distance(name, candidate);
+        if dist <= limit && best.is_none_or(|(best, _)| dist < best) {
+            best = Some((dist, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
 }
@@ -0,0 +1,197 @@
+use crate::Jacl;
+use crate::api::{view, JaclStruct, Object, Table, Map};
+use crate::dom::Structure;
+use crate::path::Segment;
+use crate::types::{Struct, Value, StructKind};
+
+/* Visitors
+ *
+ * A walk visits every structure, entry, property and tuple element in
+ * document order: the entries of a structure, then its properties. Each
+ * hook receives the path to the node it is called for. Entries are named
+ * by their key, or by their position if they are anonymous, so each path
+ * can be passed to `Jacl::get`.
+ *
+ * Every hook does nothing by default, so a visitor only implements what
+ * it is interested in.
+ */
+
+#[allow(unused_variables)]
+pub trait Visitor<'s> {
+    fn enter_object(&mut self, path: &[Segment], object: &Object<'s>) {}
+    fn leave_object(&mut self, path: &[Segment], object: &Object<'s>) {}
+
+    fn enter_table(&mut self, path: &[Segment], table: &Table<'s>) {}
+    fn leave_table(&mut self, path: &[Segment], table: &Table<'s>) {}
+
+    fn enter_map(&mut self, path: &[Segment], map: &Map<'s>) {}
+    fn leave_map(&mut self, path: &[Segment], map: &Map<'s>) {}
+
+    /// An entry, before its structure is visited. Anonymous entries have no key.
+    fn enter_entry(&mut self, path: &[Segment], key: Option<&'s str>, entry: Option<&JaclStruct<'s>>) {}
+    fn leave_entry(&mut self, path: &[Segment], key: Option<&'s str>, entry: Option<&JaclStruct<'s>>) {}
+
+    /// A property, before the elements of its value are visited
+    fn enter_property(&mut self, path: &[Segment], name: &'s str, val: &'s Value) {}
+    fn leave_property(&mut self, path: &[Segment], name: &'s str, val: &'s Value) {}
+
+    /// An element of a Tuple, before its own elements if it is a Tuple
+    fn enter_element(&mut self, path: &[Segment], idx: usize, val: &'s Value) {}
+    fn leave_element(&mut self, path: &[Segment], idx: usize, val: &'s Value) {}
+}
+
+fn walk_struct<'s, V: Visitor<'s>>(path: &mut Vec<Segment>, strct: &JaclStruct<'s>, visitor: &mut V) {
+    match strct {
+        JaclStruct::Object(object) => visitor.enter_object(path, object),
+        JaclStruct::Table(table) => visitor.enter_table(path, table),
+        JaclStruct::Map(map) => visitor.enter_map(path, map),
+    }
+
+    let jacl = strct.jacl();
+    let (entries, props) = match strct.data() {
+        Struct::Object { entries, props, .. } => (Some(entries), Some(props)),
+        Struct::Table { entries, .. } => (Some(entries), None),
+        Struct::Map { props, .. } => (None, Some(props)),
+    };
+    for (idx, (key, entry)) in entries.into_iter().flatten().enumerate() {
+        let (key, seg) = if key.starts_with('#') {
+            (None, Segment::Index(idx))
+        }
+        else {
+            (Some(key.as_str()), Segment::Key(key.clone()))
+        };
+        let entry = entry.as_ref().map(|entry| view(entry, jacl));
+        path.push(seg);
+        visitor.enter_entry(path, key, entry.as_ref());
+        if let Some(entry) = &entry {
+            walk_struct(path, entry, visitor);
+        }
+        visitor.leave_entry(path, key, entry.as_ref());
+        path.pop();
+    }
+    for (name, val) in props.into_iter().flatten() {
+        path.push(Segment::Key(name.clone()));
+        visitor.enter_property(path, name, val);
+        walk_elements(path, val, visitor);
+        visitor.leave_property(path, name, val);
+        path.pop();
+    }
+
+    match strct {
+        JaclStruct::Object(object) => visitor.leave_object(path, object),
+        JaclStruct::Table(table) => visitor.leave_table(path, table),
+        JaclStruct::Map(map) => visitor.leave_map(path, map),
+    }
+}
+
+fn walk_elements<'s, V: Visitor<'s>>(path: &mut Vec<Segment>, val: &'s Value, visitor: &mut V) {
+    if let Value::Tuple(vals) = val {
+        for (idx, val) in vals.iter().enumerate() {
+            path.push(Segment::Index(idx));
+            visitor.enter_element(path, idx, val);
+            walk_elements(path, val, visitor);
+            visitor.leave_element(path, idx, val);
+            path.pop();
+        }
+    }
+}
+
+impl Jacl {
+    /// Visit everything in the document, starting with the root Object
+    pub fn walk<'s, V: Visitor<'s>>(&'s self, visitor: &mut V) {
+        walk_struct(&mut Vec::new(), &view(self.data(), self), visitor);
+    }
+}
+
+impl<'s> JaclStruct<'s> {
+    /// Visit this structure and everything within it. Paths are relative to it.
+    pub fn walk<V: Visitor<'s>>(&self, visitor: &mut V) {
+        walk_struct(&mut Vec::new(), self, visitor);
+    }
+}
+
+/// A visitor which may change an owned Structure as it is walked. The
+/// hooks for a structure may change its contents before they are visited.
+#[allow(unused_variables)]
+pub trait VisitorMut {
+    fn enter_object(&mut self, path: &[Segment], object: &mut Structure) {}
+    fn leave_object(&mut self, path: &[Segment], object: &mut Structure) {}
+
+    fn enter_table(&mut self, path: &[Segment], table: &mut Structure) {}
+    fn leave_table(&mut self, path: &[Segment], table: &mut Structure) {}
+
+    fn enter_map(&mut self, path: &[Segment], map: &mut Structure) {}
+    fn leave_map(&mut self, path: &[Segment], map: &mut Structure) {}
+
+    /// An entry, before its structure is visited. Setting it to None
+    /// empties the entry.
+    fn enter_entry(&mut self, path: &[Segment], key: Option<&str>, entry: &mut Option<Structure>) {}
+    fn leave_entry(&mut self, path: &[Segment], key: Option<&str>, entry: &mut Option<Structure>) {}
+
+    fn enter_property(&mut self, path: &[Segment], name: &str, val: &mut Value) {}
+    fn leave_property(&mut self, path: &[Segment], name: &str, val: &mut Value) {}
+
+    fn enter_element(&mut self, path: &[Segment], idx: usize, val: &mut Value) {}
+    fn leave_element(&mut self, path: &[Segment], idx: usize, val: &mut Value) {}
+}
+
+fn walk_structure<V: VisitorMut>(path: &mut Vec<Segment>, strct: &mut Structure, visitor: &mut V) {
+    let kind = strct.kind();
+    match kind {
+        StructKind::Object => visitor.enter_object(path, strct),
+        StructKind::Table => visitor.enter_table(path, strct),
+        StructKind::Map => visitor.enter_map(path, strct),
+    }
+
+    for idx in 0..strct.entries.len() {
+        let (key, entry) = match strct.entries.get_index_mut(idx) {
+            Some(item) => item,
+            None => break,
+        };
+        let (key, seg) = if key.starts_with('#') {
+            (None, Segment::Index(idx))
+        }
+        else {
+            (Some(key.as_str()), Segment::Key(key.clone()))
+        };
+        path.push(seg);
+        visitor.enter_entry(path, key, entry);
+        if let Some(entry) = entry {
+            walk_structure(path, entry, visitor);
+        }
+        visitor.leave_entry(path, key, entry);
+        path.pop();
+    }
+    for (name, val) in strct.props.iter_mut() {
+        path.push(Segment::Key(name.clone()));
+        visitor.enter_property(path, name, val);
+        walk_elements_mut(path, val, visitor);
+        visitor.leave_property(path, name, val);
+        path.pop();
+    }
+
+    match kind {
+        StructKind::Object => visitor.leave_object(path, strct),
+        StructKind::Table => visitor.leave_table(path, strct),
+        StructKind::Map => visitor.leave_map(path, strct),
+    }
+}
+
+fn walk_elements_mut<V: VisitorMut>(path: &mut Vec<Segment>, val: &mut Value, visitor: &mut V) {
+    if let Value::Tuple(vals) = val {
+        for (idx, val) in vals.iter_mut().enumerate() {
+            path.push(Segment::Index(idx));
+            visitor.enter_element(path, idx, val);
+            walk_elements_mut(path, val, visitor);
+            visitor.leave_element(path, idx, val);
+            path.pop();
+        }
+    }
+}
+
+impl Structure {
+    /// Visit this structure and everything within it, allowing changes
+    pub fn walk_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        walk_structure(&mut Vec::new(), self, visitor);
+    }
+}
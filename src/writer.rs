@@ -0,0 +1,199 @@
+use std::fmt;
+
+use crate::types::{Value, Struct, Entries, Props};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WriteError {
+    /// A key or var which the tokeniser would not read back as a name
+    InvalidName(String),
+    /// JACL has no syntax for negative numbers
+    NegativeNumber(String),
+    /// JACL has no syntax for infinite or NaN floats
+    NonFiniteFloat(f64),
+    /// A reference to an anonymous structure, other than in its own binding
+    AnonymousReference(String),
+    /// A String which the tokeniser would not read back as written, as it
+    /// holds a newline, an unescaped quote or ends in an escape
+    UnwritableString(String),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::InvalidName(name) => write!(f, "'{}' is not a valid name", name),
+            WriteError::NegativeNumber(num) => write!(f, "Cannot write negative number {}", num),
+            WriteError::NonFiniteFloat(num) => write!(f, "Cannot write non-finite float {}", num),
+            WriteError::AnonymousReference(_) => write!(f, "Anonymous structures can only be referenced by the properties bound to them"),
+            WriteError::UnwritableString(string) => write!(f, "Cannot write {:?} as a String, as JACL reads Strings exactly as written", string),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// Could `name` be read back as a single Name token?
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !name.chars().all(|c| c.is_ascii_digit())
+        && name != "true" && name != "false"
+}
+
+pub fn write_name(name: &str) -> Result<String, WriteError> {
+    if is_valid_name(name) {
+        Ok(name.to_string())
+    }
+    else {
+        Err(WriteError::InvalidName(name.to_string()))
+    }
+}
+
+/// Quote `string`, so long as it would read back exactly as it is.
+/// Strings are not unescaped when read, so a `\` only stops the char after it
+/// from ending the String, and can't stand in for a newline.
+pub fn write_str(string: &str) -> Result<String, WriteError> {
+    let mut escaped = false;
+    for c in string.chars() {
+        if c == '\n' || (c == '"' && !escaped) {
+            return Err(WriteError::UnwritableString(string.to_string()));
+        }
+        escaped = !escaped && c == '\\';
+    }
+    if escaped {
+        return Err(WriteError::UnwritableString(string.to_string()));
+    }
+    Ok(format!("\"{}\"", string))
+}
+
+pub fn write_value(val: &Value) -> Result<String, WriteError> {
+    match val {
+        Value::Key(key) if is_anon(key) => Err(WriteError::AnonymousReference(key.to_string())),
+        Value::Key(key) => write_name(key),
+        Value::Tuple(vals) => {
+            let elems = vals.iter()
+                            .map(write_value)
+                            .collect::<Result<Vec<String>, WriteError>>()?;
+            Ok(format!("({})", elems.join(", ")))
+        },
+        Value::String(string) => write_str(string),
+        Value::Integer(integer) => {
+            if *integer < 0 {
+                Err(WriteError::NegativeNumber(integer.to_string()))
+            }
+            else {
+                Ok(integer.to_string())
+            }
+        },
+        Value::Float(float) => {
+            if !float.is_finite() {
+                Err(WriteError::NonFiniteFloat(*float))
+            }
+            else if float.is_sign_negative() && *float != 0.0 {
+                Err(WriteError::NegativeNumber(float.to_string()))
+            }
+            else {
+                // Display never uses an exponent, but drops the point from whole numbers
+                let text = float.abs().to_string();
+                if text.contains('.') { Ok(text) } else { Ok(format!("{}.0", text)) }
+            }
+        },
+        Value::Boolean(boolean) => Ok(boolean.to_string()),
+    }
+}
+
+/* Write Documents */
+
+const INDENT: &str = "    ";
+
+fn is_anon(key: &str) -> bool {
+    key.starts_with('#')
+}
+
+/// Write `data`, the root Object of a document, as JACL text with each
+/// item on its own line. Otherwise, the whole document is written on one
+/// line, with items separated by `;`.
+pub fn write_document(data: &Struct, pretty: bool) -> Result<String, WriteError> {
+    let items = match data {
+        Struct::Object { entries, props, .. } => write_items(Some(entries), Some(props), 0, pretty)?,
+        Struct::Table { entries, .. } => write_items(Some(entries), None, 0, pretty)?,
+        Struct::Map { props, .. } => write_items(None, Some(props), 0, pretty)?,
+    };
+    if pretty {
+        Ok(items.iter().map(|item| format!("{}\n", item)).collect::<String>())
+    }
+    else {
+        Ok(items.join("; "))
+    }
+}
+
+/// Write each item of a structure. Properties come first, except those
+/// bound to anonymous structures, which are written along with the
+/// structure in its place among the entries. An anonymous structure no
+/// property is bound to, such as one whose property was rebound, is
+/// written bare, as in `{ ... }`.
+fn write_items(entries: Option<&Entries>, props: Option<&Props>, depth: usize, pretty: bool) -> Result<Vec<String>, WriteError> {
+    let mut items = Vec::new();
+    if let Some(props) = props {
+        for (name, val) in props {
+            match val {
+                Value::Key(key) if is_anon(key) => {
+                    if !entries.is_some_and(|entries| entries.contains_key(key)) {
+                        return Err(WriteError::AnonymousReference(key.to_string()));
+                    }
+                },
+                _ => {
+                    items.push(format!("{} = {}", write_name(name)?, write_value(val)?));
+                },
+            }
+        }
+    }
+
+    if let Some(entries) = entries {
+        for (key, entry) in entries {
+            let mut item = String::new();
+            if is_anon(key) {
+                let names = props.into_iter()
+                                 .flatten()
+                                 .filter(|(_, val)| matches!(val, Value::Key(bound) if bound == key))
+                                 .map(|(name, _)| write_name(name))
+                                 .collect::<Result<Vec<String>, WriteError>>()?;
+                if !names.is_empty() {
+                    item.push_str(&format!("{} = ", names.join(", ")));
+                }
+            }
+            else {
+                item.push_str(&write_name(key)?);
+                if entry.is_some() {
+                    item.push(' ');
+                }
+            }
+            if let Some(strct) = entry {
+                item.push_str(&write_struct(strct, depth, pretty)?);
+            }
+            items.push(item);
+        }
+    }
+    Ok(items)
+}
+
+pub(crate) fn write_struct(strct: &Struct, depth: usize, pretty: bool) -> Result<String, WriteError> {
+    let (open, close, entries, props) = match strct {
+        Struct::Object { entries, props, .. } => ("{", "}", Some(entries), Some(props)),
+        Struct::Table { entries, .. } => ("[", "]", Some(entries), None),
+        Struct::Map { props, .. } => ("{%", "%}", None, Some(props)),
+    };
+    let items = write_items(entries, props, depth + 1, pretty)?;
+    if items.is_empty() {
+        Ok(format!("{} {}", open, close))
+    }
+    else if pretty {
+        let indent = INDENT.repeat(depth + 1);
+        let body = items.iter()
+                        .map(|item| format!("{}{}\n", indent, item))
+                        .collect::<String>();
+        Ok(format!("{}\n{}{}{}", open, body, INDENT.repeat(depth), close))
+    }
+    else {
+        Ok(format!("{} {} {}", open, items.join("; "), close))
+    }
+}
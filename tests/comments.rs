@@ -0,0 +1,29 @@
+use jacl::{read_string, PropertyStruct, Value};
+
+#[test]
+fn comments_run_to_the_end_of_the_line() {
+    let jacl = read_string("# Leading\na = 1 # Trailing\n  # Indented\nb = 2 # At the end").unwrap();
+    let root = jacl.root();
+    assert!(matches!(root.get_property("a"), Some(Value::Integer(1))));
+    assert!(matches!(root.get_property("b"), Some(Value::Integer(2))));
+}
+
+#[test]
+fn hashes_within_strings_are_not_comments() {
+    let jacl = read_string("s = \"a # b\"\n").unwrap();
+    assert!(matches!(jacl.root().get_property("s"), Some(Value::String(s)) if s == "a # b"));
+}
+
+#[test]
+fn comments_end_items() {
+    assert!(read_string("a # comment\nb = 1\n").is_ok());
+    assert!(read_string("a = 1 # b = 2\n").unwrap().root().get_property("b").is_none());
+}
+
+#[test]
+fn unterminated_strings_are_reported() {
+    let err = read_string("a = 1\nb = \"open").unwrap_err();
+    assert_eq!(err.errors().len(), 1);
+    assert_eq!(err.errors()[0].message(), "Unterminated String");
+    assert_eq!(err.errors()[0].location(), Some((2, 5)));
+}
@@ -0,0 +1,31 @@
+use jacl::{read_string, DomError, Structure, Value};
+
+#[test]
+fn equality_ignores_how_anonymous_entries_were_numbered() {
+    let mut a = Structure::object();
+    a.insert_entry("x", None).unwrap();
+    a.remove_entry("x").unwrap();
+    a.bind_structure("p", Structure::map()).unwrap();
+
+    let mut b = Structure::object();
+    b.bind_structure("p", Structure::map()).unwrap();
+    assert_eq!(a, b);
+
+    b.push_entry(Structure::table()).unwrap();
+    assert_ne!(a, b);
+}
+
+#[test]
+fn anonymous_entries_have_no_key() {
+    let jacl = read_string("x = { a = 1 }\n{ }\n").unwrap();
+    let mut strct = Structure::from(&jacl);
+    for key in &["#anon0", "#anon1"] {
+        assert!(strct.get_entry(key).is_none());
+        assert!(strct.get_entry_mut(key).is_none());
+        assert_eq!(strct.remove_entry(key), Err(DomError::NotFound(key.to_string())));
+    }
+    assert_eq!(strct.entries().count(), 2);
+    assert_eq!(strct.resolve_property("x").unwrap().get_property("a"), Some(&Value::Integer(1)));
+    strct.resolve_property_mut("x").unwrap().insert_property("a", 2).unwrap();
+    assert_eq!(strct.resolve_property("x").unwrap().get_property("a"), Some(&Value::Integer(2)));
+}
@@ -0,0 +1,17 @@
+use jacl::{EditError, SyntaxTree};
+
+#[test]
+fn removing_a_property_removes_every_binding() {
+    let mut tree = SyntaxTree::parse("a = 1\nb, a = 2\nc { }\na = 3\n");
+    tree.remove_property("a").unwrap();
+    assert_eq!(tree.text(), "b = 2\nc { }\n");
+}
+
+#[test]
+fn failed_removals_leave_the_text_alone() {
+    for src in &["a = 1\nb = $a\na = 2\n", "a, b = 1\nc = $a\na = 2\n"] {
+        let mut tree = SyntaxTree::parse(src);
+        assert!(matches!(tree.remove_property("a"), Err(EditError::Invalid(_))));
+        assert_eq!(tree.text(), *src);
+    }
+}
@@ -0,0 +1,52 @@
+use jacl::{read_string, format_string, FormatConfig};
+
+const DOCS: [&str; 5] = [
+    include_str!("../src/test.jacl"),
+    "b = { }\nb = 1\n",
+    "x = { }\ny = $x\nz = 1\n",
+    "a = 1\nb = $a\nc { }\na = 2\n",
+    "e [ { } ]\nv, w = { }\nw = (1, 2) # pair\nu = \"u\"\n",
+];
+
+fn configs() -> Vec<FormatConfig> {
+    let props_first = FormatConfig { props_first: true, ..FormatConfig::default() };
+    let tabs = FormatConfig {
+        indent: String::from("\t"),
+        align_equals: true,
+        blank_around_blocks: true,
+        max_width: 20,
+        props_first: true,
+        tab_width: 8,
+        ..FormatConfig::default()
+    };
+    vec![FormatConfig::default(), props_first, tabs]
+}
+
+#[test]
+fn formatting_keeps_meaning() {
+    for config in configs() {
+        for doc in DOCS.iter() {
+            let out = format_string(doc, &config).expect("Document did not format");
+            let back = read_string(&out).unwrap_or_else(|err| panic!("Output did not parse: {:?}\n{}", err.errors(), out));
+            assert!(back == read_string(doc).unwrap(), "Formatting changed the meaning of:\n{}\ninto:\n{}", doc, out);
+        }
+    }
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    for config in configs() {
+        for doc in DOCS.iter() {
+            let once = format_string(doc, &config).expect("Document did not format");
+            let twice = format_string(&once, &config).expect("Output did not format");
+            assert_eq!(once, twice);
+        }
+    }
+}
+
+#[test]
+fn props_first_keeps_rebindings_in_order() {
+    let config = FormatConfig { props_first: true, ..FormatConfig::default() };
+    assert_eq!(format_string("b = { }\nb = 1\n", &config).unwrap(), "b = {}\nb = 1\n");
+    assert_eq!(format_string("c { }\na = 1\n", &config).unwrap(), "a = 1\nc {}\n");
+}
@@ -0,0 +1,21 @@
+use jacl::{read_string, PathErrorKind};
+
+fn similar(src: &str, path: &str) -> Option<String> {
+    let jacl = read_string(src).unwrap();
+    match jacl.get(path).map(|_| ()).unwrap_err().kind {
+        PathErrorKind::NotFound { similar } => similar,
+        kind => panic!("Expected NotFound, got {:?}", kind),
+    }
+}
+
+#[test]
+fn suggests_similar_names() {
+    assert_eq!(similar("port = 1\nhost { }\n", "prt"), Some(String::from("port")));
+    assert_eq!(similar("port = 1\nhost { }\n", "hst"), Some(String::from("host")));
+}
+
+#[test]
+fn never_suggests_anonymous_keys() {
+    assert_eq!(similar("x = { }\n{ }\n", "anon0"), None);
+    assert_eq!(similar("t [ { } { } ]\n", "t.anon1"), None);
+}
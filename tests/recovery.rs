@@ -0,0 +1,41 @@
+use jacl::{read_string, read_string_partial, ErrorCode, JaclError};
+
+/// The code and location of every error reported
+fn codes(err: Option<JaclError<'_>>) -> Vec<(ErrorCode, (usize, usize))> {
+    err.expect("Source parsed without errors")
+       .errors()
+       .iter()
+       .map(|err| (err.code().unwrap(), err.location().unwrap()))
+       .collect()
+}
+
+#[test]
+fn reports_every_lexical_error() {
+    let (jacl, err) = read_string_partial("a = 1\nb = ~\nc = 99999999999999999999\nd = \"x\ne = 2\n");
+    assert_eq!(codes(err), vec![
+        (ErrorCode::UnexpectedCharacter, (2, 5)),
+        (ErrorCode::IntegerTooLarge, (3, 5)),
+        (ErrorCode::UnexpectedNewline, (4, 7)),
+    ]);
+    assert!(jacl == read_string("a = 1\ne = 2\n").unwrap());
+}
+
+#[test]
+fn reports_every_syntax_error() {
+    let (jacl, err) = read_string_partial("a = 1\nb = \nc = 2\nd { e = }\nf = 3\n");
+    assert_eq!(codes(err), vec![
+        (ErrorCode::ExpectedValue, (2, 5)),
+        (ErrorCode::ExpectedValue, (4, 9)),
+    ]);
+    assert!(jacl == read_string("a = 1\nc = 2\nd { }\nf = 3\n").unwrap());
+}
+
+#[test]
+fn keeps_the_items_before_the_input_ends() {
+    let (jacl, err) = read_string_partial("a = 1\nb = )\nc { x = 1\n");
+    assert_eq!(codes(err), vec![
+        (ErrorCode::ExpectedValue, (2, 5)),
+        (ErrorCode::ExpectedTokenAtEnd, (3, 10)),
+    ]);
+    assert!(jacl == read_string("a = 1\nc { x = 1 }\n").unwrap());
+}
@@ -0,0 +1,78 @@
+use jacl::{infer_schema, read_string, EntryStruct, ErrorCode, Origin, PropertyStruct, Schema, Value};
+
+const SCHEMA: &str = r#"
+props {
+    name { type = String; min_length = 2; pattern = "^[a-z]+$" }
+    port { type = Integer; min = 1; max = 65535 }
+    mode { one_of = (fast, slow) }
+    ssl { type = Boolean; default = false }
+}
+entries {
+    server = Object
+    extra { type = Object; required = false; default = { x = 1 } }
+}
+"#;
+
+/// The code and location of every violation of SCHEMA in `src`
+fn violations(src: &str) -> Vec<(ErrorCode, (usize, usize))> {
+    let schema = Schema::parse(SCHEMA).unwrap();
+    schema.check(&read_string(src).unwrap())
+          .iter()
+          .map(|violation| (violation.error().code().unwrap(), violation.error().location().unwrap()))
+          .collect()
+}
+
+#[test]
+fn invalid_schemas_are_rejected() {
+    let err = Schema::parse("entries { a { type = Nope } }\n").unwrap_err();
+    let codes: Vec<_> = err.errors().iter().map(|err| (err.code(), err.location())).collect();
+    assert_eq!(codes, vec![(Some(ErrorCode::InvalidSchema), Some((1, 22)))]);
+}
+
+#[test]
+fn valid_documents_have_no_violations() {
+    assert_eq!(violations("name = \"ab\"\nport = 80\nmode = fast\nserver { }\n"), vec![]);
+}
+
+#[test]
+fn each_kind_of_violation_is_reported() {
+    assert_eq!(violations("name = 1\nport = 80\nmode = fast\nserver { }\n"),
+               vec![(ErrorCode::WrongType, (1, 8))]);
+    assert_eq!(violations("name = \"ab\"\nmode = fast\n"),
+               vec![(ErrorCode::MissingItem, (1, 1)), (ErrorCode::MissingItem, (1, 1))]);
+    assert_eq!(violations("name = \"ab\"\nport = 80\nmode = fast\nserver { }\nother = 1\n"),
+               vec![(ErrorCode::UnexpectedItem, (5, 1))]);
+    assert_eq!(violations("name = \"ab\"\nport = 0\nmode = fast\nserver { }\n"),
+               vec![(ErrorCode::OutOfRange, (2, 8))]);
+    assert_eq!(violations("name = \"a\"\nport = 80\nmode = fast\nserver { }\n"),
+               vec![(ErrorCode::WrongLength, (1, 8))]);
+    assert_eq!(violations("name = \"ab\"\nport = 80\nmode = medium\nserver { }\n"),
+               vec![(ErrorCode::NotOneOf, (3, 8))]);
+    assert_eq!(violations("name = \"AB\"\nport = 80\nmode = fast\nserver { }\n"),
+               vec![(ErrorCode::NoPatternMatch, (1, 8))]);
+}
+
+#[test]
+fn defaults_are_filled_in_and_marked() {
+    let schema = Schema::parse(SCHEMA).unwrap();
+    let mut jacl = read_string("name = \"ab\"\nport = 80\nmode = fast\nserver { }\n").unwrap();
+    schema.apply_defaults(&mut jacl);
+    let root = jacl.root();
+    assert_eq!(root.get_property("ssl"), Some(&Value::Boolean(false)));
+    assert_eq!(root.property_origin("ssl"), Some(Origin::Default));
+    assert_eq!(root.property_origin("name"), Some(Origin::Document));
+    let extra = root.get_entry("extra").unwrap();
+    assert_eq!(extra.as_property_struct().unwrap().get_property("x"), Some(&Value::Integer(1)));
+    assert_eq!(root.entry_origin("extra"), Some(Origin::Default));
+    assert_eq!(root.entry_origin("server"), Some(Origin::Document));
+    assert_eq!(schema.check(&jacl).len(), 0);
+}
+
+#[test]
+fn inferred_schemas_accept_their_examples() {
+    let jacl = read_string(include_str!("../src/test.jacl")).unwrap();
+    let text = infer_schema(vec![&jacl]);
+    let schema = Schema::parse(&text).unwrap_or_else(|err| panic!("Inferred schema did not parse: {:?}\n{}", err.errors(), text));
+    let found: Vec<_> = schema.check(&jacl).iter().map(|violation| violation.message().to_string()).collect();
+    assert!(found.is_empty(), "Violations of the inferred schema: {:?}\n{}", found, text);
+}
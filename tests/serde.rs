@@ -0,0 +1,99 @@
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Radio {
+    stations: Stations,
+    alarms: Vec<Alarm>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Stations {
+    weekdays: String,
+    weekends: Station,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Station {
+    name: String,
+    broadcaster: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Alarm {
+    days: String,
+    time: String,
+    alarm: String,
+    volume: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Freq {
+    Off,
+    Fm(u32),
+    Dab { block: String },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    volume: f64,
+    enabled: bool,
+    pair: (u8, String),
+    freqs: Vec<Freq>,
+    note: Option<String>,
+    tags: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Note {
+    text: String,
+}
+
+/// Serialize `val` and check it deserializes back the same
+fn round_trip<T>(val: &T)
+where
+    T: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug,
+{
+    let out = jacl::to_string(val).expect("Value could not be serialized");
+    let back: T = jacl::from_str(&out).unwrap_or_else(|err| panic!("Output did not deserialize: {}\n{}", err, out));
+    assert_eq!(&back, val, "Output read back differently:\n{}", out);
+}
+
+#[test]
+fn test_file_deserializes() {
+    let radio: Radio = jacl::from_str(include_str!("../src/test.jacl")).unwrap();
+    assert_eq!(radio.stations.weekdays, "r4");
+    assert_eq!(radio.stations.weekends, Station { name: String::from("P6 Beat"), broadcaster: String::from("DR") });
+    assert_eq!(radio.alarms.len(), 2);
+    assert_eq!(radio.alarms[1].alarm, "pulsar");
+    assert_eq!(radio.alarms[1].volume, 5.9);
+}
+
+#[test]
+fn structures_round_trip() {
+    let mut tags = BTreeMap::new();
+    tags.insert(String::from("first"), 1);
+    tags.insert(String::from("second"), 2);
+    round_trip(&Config {
+        name: String::from("C:\\radio"),
+        volume: 3.5,
+        enabled: true,
+        pair: (7, String::from("seven")),
+        freqs: vec![Freq::Off, Freq::Fm(93), Freq::Dab { block: String::from("12B") }],
+        note: Some(String::from("#not a key")),
+        tags,
+    });
+}
+
+#[test]
+fn unwritable_strings_are_refused() {
+    for text in &["two\nlines", "say \"hi\"", "ends in \\"] {
+        let err = jacl::to_string(&Note { text: text.to_string() }).unwrap_err();
+        assert!(err.to_string().contains("as a String"), "Unexpected error: {}", err);
+    }
+    round_trip(&Note { text: String::from("say \\\"hi\\\"") });
+}
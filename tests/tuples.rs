@@ -0,0 +1,31 @@
+use jacl::{read_string, PropertyStruct, SyntaxTree, Value};
+
+const SPLIT: &str = "t = (\n    1,\n    (2,\n     3)\n    , 4\n)\nu = 5\n";
+
+#[test]
+fn tuples_may_span_lines() {
+    let jacl = read_string(SPLIT).unwrap();
+    let root = jacl.root();
+    match root.get_property("t") {
+        Some(Value::Tuple(elems)) => {
+            assert_eq!(elems.len(), 3);
+            assert!(matches!(elems[1], Value::Tuple(ref inner) if inner.len() == 2));
+            assert!(matches!(elems[2], Value::Integer(4)));
+        },
+        other => panic!("Expected a Tuple, got {:?}", other),
+    }
+    assert!(matches!(root.get_property("u"), Some(Value::Integer(5))));
+}
+
+#[test]
+fn split_tuples_are_kept_in_the_syntax_tree() {
+    let tree = SyntaxTree::parse(SPLIT);
+    assert_eq!(tree.text(), SPLIT);
+    assert!(tree.lower().is_ok());
+}
+
+#[test]
+fn line_breaks_still_end_bindings() {
+    assert!(read_string("t = (1, 2\nu = 3\n").is_err());
+    assert!(read_string("t =\n(1, 2)\n").is_err());
+}
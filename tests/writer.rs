@@ -0,0 +1,48 @@
+use jacl::{read_string, write_string, EntryStruct, PropertyStruct, Value};
+
+/// Write `src` out and check it reads back as the same document
+fn round_trip(src: &str) {
+    let jacl = read_string(src).expect("Source did not parse");
+    let out = write_string(&jacl).expect("Document could not be written");
+    let back = read_string(&out).unwrap_or_else(|err| panic!("Output did not parse: {:?}\n{}", err.errors(), out));
+    assert!(back == jacl, "Output read back differently:\n{}", out);
+}
+
+#[test]
+fn test_file_round_trips() {
+    round_trip(include_str!("../src/test.jacl"));
+}
+
+#[test]
+fn anonymous_entries_round_trip() {
+    round_trip("x = { a = 1 }\ny, z = [ { } ]\n{ b = 2 }\n");
+    round_trip("t [ { a = 1 } ]\nt [ { b = 2 } ]\n");
+}
+
+#[test]
+fn redefined_blocks_round_trip() {
+    round_trip("a { _y = {% %} }\na { _y [ ]\n _y = { } }\n");
+    round_trip("a { x = { } }\na { y = { q = 1 } }\n");
+}
+
+#[test]
+fn rebound_properties_round_trip() {
+    round_trip("x = { }\ny = { }\nx = 1\nz = { }\n");
+    round_trip("a, b = { }\na = 2\n{ }\n");
+    round_trip("x = { a = 1 }\nx = 2\n");
+}
+
+#[test]
+fn rebinding_keeps_the_anonymous_entry() {
+    let jacl = read_string("x = { a = 1 }\nx = 2\n").unwrap();
+    assert_eq!(jacl.root().entries().len(), 1);
+    assert_eq!(write_string(&jacl).unwrap(), "x = 2\n{\n    a = 1\n}\n");
+}
+
+#[test]
+fn strings_are_written_as_read() {
+    let src = "a = \"C:\\new\"\nb = \"say \\\"hi\\\"\"\nc = \"\\\\\"\n";
+    let jacl = read_string(src).unwrap();
+    assert_eq!(jacl.root().get_property("a"), Some(&Value::String(String::from("C:\\new"))));
+    assert_eq!(write_string(&jacl).unwrap(), src);
+}